@@ -8,6 +8,11 @@ pub struct SimpleBarAllocator {
     mem32_iter: u32,
     mem64: Range<u64>,
     mem64_iter: u64,
+    /// Prefetchable window, empty unless set.
+    mem64_pref: Range<u64>,
+    mem64_pref_iter: u64,
+    io: Range<u32>,
+    io_iter: u32,
 }
 
 impl SimpleBarAllocator {
@@ -17,6 +22,10 @@ impl SimpleBarAllocator {
             mem64: mem64_start..mem64_start + mem64_size,
             mem32_iter: mem32_start,
             mem64_iter: mem64_start,
+            mem64_pref: 0..0,
+            mem64_pref_iter: 0,
+            io: 0..0,
+            io_iter: 0,
         }
     }
 
@@ -29,13 +38,43 @@ impl SimpleBarAllocator {
         self.mem64 = start..start + size;
         self.mem64_iter = start;
     }
+
+    /// Sets the I/O port window I/O BARs are allocated from.
+    pub fn set_io(&mut self, base: u32, size: u32) {
+        self.io = base..base + size;
+        self.io_iter = base;
+    }
+
+    /// Free bytes left in the 32-bit and the 64-bit windows, the latter
+    /// including the prefetchable one. Each window is handed out bottom-up,
+    /// so the free space is contiguous. An allocation that fails although it
+    /// is smaller than what is reported here failed on alignment padding, a
+    /// sign the window is too small or BARs should be allocated largest
+    /// first.
+    pub fn remaining(&self) -> (u64, u64) {
+        let mem32 = self.mem32.end.saturating_sub(self.mem32_iter) as u64;
+        let mem64 = self.mem64.end.saturating_sub(self.mem64_iter)
+            + self.mem64_pref.end.saturating_sub(self.mem64_pref_iter);
+        (mem32, mem64)
+    }
+
+    /// Sets the prefetchable 64-bit window. Prefetchable BARs are placed here
+    /// first and fall back to the `set_mem64` window once it is full.
+    pub fn set_mem64_prefetchable(&mut self, start: u64, size: u64) {
+        self.mem64_pref = start..start + size;
+        self.mem64_pref_iter = start;
+    }
 }
 
 impl BarAllocator for SimpleBarAllocator {
     fn alloc_memory32(&mut self, size: u32) -> Option<u32> {
-        let ptr = self.mem32_iter.align_up(size);
+        // BARs are naturally aligned, their size is a power of two.
+        if !size.is_power_of_two() {
+            return None;
+        }
+        let ptr = self.mem32_iter.checked_align_up(size)?;
 
-        if self.mem32.contains(&ptr) && ptr + size <= self.mem32.end {
+        if self.mem32.contains(&ptr) && ptr.checked_add(size)? <= self.mem32.end {
             self.mem32_iter = ptr + size;
             Some(ptr)
         } else {
@@ -44,28 +83,128 @@ impl BarAllocator for SimpleBarAllocator {
     }
 
     fn alloc_memory64(&mut self, size: u64) -> Option<u64> {
-        let ptr = self.mem64_iter.align_up(size);
-        if self.mem64.contains(&ptr) && ptr + size <= self.mem64.end {
-            self.mem64_iter = ptr + size;
-            Some(ptr)
+        alloc_from(&self.mem64, &mut self.mem64_iter, size)
+    }
+
+    fn alloc_memory64_prefetchable(&mut self, size: u64) -> Option<u64> {
+        alloc_from(&self.mem64_pref, &mut self.mem64_pref_iter, size)
+            .or_else(|| self.alloc_memory64(size))
+    }
+
+    fn alloc_io(&mut self, size: u32) -> Option<u32> {
+        if !size.is_power_of_two() {
+            return None;
+        }
+        let port = self.io_iter.checked_align_up(size)?;
+        if self.io.contains(&port) && port.checked_add(size)? <= self.io.end {
+            self.io_iter = port + size;
+            Some(port)
         } else {
             None
         }
     }
 }
 
-trait Algin {
-    fn align_up(self, align: Self) -> Self;
+fn alloc_from(window: &Range<u64>, iter: &mut u64, size: u64) -> Option<u64> {
+    if !size.is_power_of_two() {
+        return None;
+    }
+    let ptr = iter.checked_align_up(size)?;
+    if window.contains(&ptr) && ptr.checked_add(size)? <= window.end {
+        *iter = ptr + size;
+        Some(ptr)
+    } else {
+        None
+    }
+}
+
+trait Algin: Sized {
+    /// Rounds up to a multiple of `align`, a power of two. `None` on
+    /// overflow.
+    fn checked_align_up(self, align: Self) -> Option<Self>;
 }
 
 impl Algin for u32 {
-    fn align_up(self, align: Self) -> Self {
-        (self + align - 1) & !(align - 1)
+    fn checked_align_up(self, align: Self) -> Option<Self> {
+        Some(self.checked_add(align - 1)? & !(align - 1))
     }
 }
 
 impl Algin for u64 {
-    fn align_up(self, align: Self) -> Self {
-        (self + align - 1) & !(align - 1)
+    fn checked_align_up(self, align: Self) -> Option<Self> {
+        Some(self.checked_add(align - 1)? & !(align - 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn aligns_misaligned_sizes_in_sequence() {
+        let mut a = SimpleBarAllocator::new(0x1000_0000, 0x10_0000, 0, 0);
+        assert_eq!(a.alloc_memory32(0x100), Some(0x1000_0000));
+        assert_eq!(a.alloc_memory32(0x4000), Some(0x1000_4000));
+        assert_eq!(a.alloc_memory32(0x10), Some(0x1000_8000));
+        assert_eq!(a.alloc_memory32(0x1000), Some(0x1000_9000));
+        assert_eq!(a.remaining().0, 0x10_0000 - 0xa000);
+    }
+
+    #[test]
+    fn rejects_sizes_that_are_not_a_power_of_two() {
+        let mut a = SimpleBarAllocator::new(0x1000_0000, 0x10_0000, 0x1_0000_0000, 0x10_0000);
+        assert_eq!(a.alloc_memory32(0x300), None);
+        assert_eq!(a.alloc_memory64(0), None);
+        assert_eq!(a.remaining(), (0x10_0000, 0x10_0000));
+    }
+
+    #[test]
+    fn exhausts_the_window() {
+        let mut a = SimpleBarAllocator::new(0x1000_0000, 0x2000, 0, 0);
+        assert_eq!(a.alloc_memory32(0x1000), Some(0x1000_0000));
+        assert_eq!(a.alloc_memory32(0x1000), Some(0x1000_1000));
+        assert_eq!(a.alloc_memory32(0x1000), None);
+        assert_eq!(a.remaining().0, 0);
+    }
+
+    #[test]
+    fn padding_failure_is_observable() {
+        let mut a = SimpleBarAllocator::new(0x1000_0000, 0x3000, 0, 0);
+        assert_eq!(a.alloc_memory32(0x1000), Some(0x1000_0000));
+        // 0x2000 are free, but a 0x2000 BAR has to start at 0x1000_2000.
+        assert_eq!(a.remaining().0, 0x2000);
+        assert_eq!(a.alloc_memory32(0x2000), None);
+        assert_eq!(a.remaining().0, 0x2000);
+    }
+
+    #[test]
+    fn stays_within_window_bounds() {
+        let mut a = SimpleBarAllocator::new(0x1000_0000, 0x1000, 0x1_0000_0000, 0x1000);
+        assert_eq!(a.alloc_memory32(0x2000), None);
+        assert_eq!(a.alloc_memory64(0x2000), None);
+        // Near the top of the address space aligning must not wrap around.
+        a.set_mem32(0xffff_f000, 0xfff);
+        assert_eq!(a.alloc_memory32(0x1000), None);
+        assert_eq!(a.alloc_memory32(0x800), Some(0xffff_f000));
+        assert_eq!(a.alloc_memory32(0x1000), None);
+    }
+
+    #[test]
+    fn prefetchable_falls_back_to_the_64bit_window() {
+        let mut a = SimpleBarAllocator::new(0, 0, 0x1_0000_0000, 0x10_0000);
+        a.set_mem64_prefetchable(0x2_0000_0000, 0x1000);
+        assert_eq!(a.alloc_memory64_prefetchable(0x1000), Some(0x2_0000_0000));
+        assert_eq!(a.alloc_memory64_prefetchable(0x1000), Some(0x1_0000_0000));
+        assert_eq!(a.remaining().1, 0x10_0000 - 0x1000);
+    }
+
+    #[test]
+    fn allocates_io_ports_only_from_a_configured_window() {
+        let mut a = SimpleBarAllocator::default();
+        assert_eq!(a.alloc_io(0x20), None);
+        a.set_io(0x1000, 0x100);
+        assert_eq!(a.alloc_io(0x20), Some(0x1000));
+        assert_eq!(a.alloc_io(0x80), Some(0x1080));
+        assert_eq!(a.alloc_io(0x80), None);
     }
 }
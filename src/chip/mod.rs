@@ -1,6 +1,6 @@
 use core::ptr::NonNull;
 
-use crate::PciAddress;
+use crate::{ConfigValue, PciAddress};
 
 pub mod generic;
 
@@ -18,4 +18,24 @@ pub trait Chip: Send {
     ///
     /// `address` and `offset` must be valid for PCI writes.
     unsafe fn write(&self, mmio_base: NonNull<u8>, address: PciAddress, offset: u16, value: u32);
+
+    /// Performs an 8-bit PCI read at `address` with `offset`.
+    ///
+    /// # Safety
+    ///
+    /// `address` and `offset` must be valid for PCI reads.
+    unsafe fn read_u8(&self, mmio_base: NonNull<u8>, address: PciAddress, offset: u16) -> u8 {
+        let data = self.read(mmio_base, address, offset & !0b11);
+        u8::extract(data, offset & 0b11)
+    }
+
+    /// Performs a 16-bit PCI read at `address` with `offset`.
+    ///
+    /// # Safety
+    ///
+    /// `address` and `offset` must be valid for PCI reads and 2 byte aligned.
+    unsafe fn read_u16(&self, mmio_base: NonNull<u8>, address: PciAddress, offset: u16) -> u16 {
+        let data = self.read(mmio_base, address, offset & !0b11);
+        u16::extract(data, offset & 0b10)
+    }
 }
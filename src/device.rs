@@ -0,0 +1,42 @@
+/// (vendor id, device id) pairs the driver supports.
+pub const SUPPORTED_DEVICES: &[(u16, u16)] = &[(0x8086, 0x10C9), (0x8086, 0x1533)];
+
+/// NIC families handled by this driver, with the silicon revision so quirks
+/// can depend on the stepping.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceKind {
+    I82576 { revision: u8 },
+    I210 { revision: u8 },
+}
+
+impl DeviceKind {
+    pub fn detect(vendor_id: u16, device_id: u16, revision: u8) -> Option<Self> {
+        if vendor_id != 0x8086 {
+            return None;
+        }
+        match device_id {
+            0x10C9 => Some(DeviceKind::I82576 { revision }),
+            0x1533 => Some(DeviceKind::I210 { revision }),
+            _ => None,
+        }
+    }
+
+    pub fn revision(&self) -> u8 {
+        match *self {
+            DeviceKind::I82576 { revision } | DeviceKind::I210 { revision } => revision,
+        }
+    }
+
+    /// Number of RSS redirection table entries. Both families have 128, each
+    /// steering one hash bucket to a queue.
+    pub fn reta_size(&self) -> usize {
+        match self {
+            DeviceKind::I82576 { .. } | DeviceKind::I210 { .. } => 128,
+        }
+    }
+
+    /// Early i210 steppings take longer to come out of reset.
+    pub fn needs_extra_reset_delay(&self) -> bool {
+        matches!(self, DeviceKind::I210 { revision: 0 })
+    }
+}
@@ -1,19 +1,28 @@
-#![no_std]
+#![cfg_attr(not(test), no_std)]
+#![recursion_limit = "256"]
 
 extern crate alloc;
 
 mod bar_alloc;
 mod chip;
+mod device;
 pub mod err;
 pub mod mac;
 pub mod osal;
 pub mod phy;
 mod root;
+pub mod rx;
+pub mod tx;
 mod types;
-use core::{cell::RefCell, ptr::NonNull};
+use alloc::boxed::Box;
+use core::{cell::RefCell, ptr::NonNull, task::Waker, time::Duration};
 use log::debug;
-pub use mac::{MacAddr6, MacStatus};
+pub use mac::{
+    Cause, FlowFilter, InterruptCause, InterruptMask, InterruptSummary, MacAddr6, MacStatus,
+    ManagementFilters, ModerationPreset, QueueStatistics, Speed, StatId, WakeStatus,
+};
 pub use osal::*;
+pub use phy::LinkSetup;
 
 pub use chip::{
     generic::{Generic, RootComplexGeneric},
@@ -21,17 +30,69 @@ pub use chip::{
 };
 
 pub use bar_alloc::*;
-pub use root::{EnumElem, RootComplex};
+pub use device::DeviceKind;
+pub use root::{BarMode, ConfigValue, EnumElem, MappedDevice, ProbedDevice, RootComplex};
+pub use rx::{
+    AdvRxWriteback, PacketType, RxBufferProvider, RxDescriptor, RxErrors, RxFrame, RxRing, RxStatus,
+};
+pub use tx::{TxCommand, TxDescriptor, TxRing, TxStatus};
 pub use types::*;
 
 pub trait BarAllocator {
     fn alloc_memory32(&mut self, size: u32) -> Option<u32>;
     fn alloc_memory64(&mut self, size: u64) -> Option<u64>;
+
+    /// Allocates for a prefetchable 64-bit BAR, which some root complexes
+    /// only forward from their prefetchable window. Defaults to
+    /// [`BarAllocator::alloc_memory64`].
+    fn alloc_memory64_prefetchable(&mut self, size: u64) -> Option<u64> {
+        self.alloc_memory64(size)
+    }
+
+    /// Allocates I/O port space. Defaults to `None`, for allocators without
+    /// an I/O window; I/O BARs are then left unassigned.
+    fn alloc_io(&mut self, size: u32) -> Option<u32> {
+        let _ = size;
+        None
+    }
+}
+
+/// MTU unless set with [`Igb::set_mtu`].
+pub const DEFAULT_MTU: usize = 1500;
+const ETH_HEADER_LEN: usize = 14;
+const VLAN_TAG_LEN: usize = 4;
+
+/// Polls of the descriptor status in [`Igb::send`], about 100ms in total.
+const TX_DONE_TRIES: usize = 10_000;
+
+/// Stands in for a missing buffer provider when freeing a receive ring.
+struct NoRxBuffers;
+
+impl RxBufferProvider for NoRxBuffers {
+    fn alloc(&mut self, _size: usize) -> Option<DmaRegion> {
+        None
+    }
 }
 
 pub struct Igb {
     mac: RefCell<mac::Mac>,
     phy: phy::Phy,
+    dma_mask: DmaMask,
+    tx_watchdog: tx::TxWatchdog,
+    reset_timeout: Duration,
+    autoneg_timeout: Duration,
+    mtu: usize,
+    link_changes: u32,
+    /// [`Kernel::now`] of the last link-up transition, `None` while down.
+    link_up_at: Option<Duration>,
+    rx: Option<RxRing>,
+    tx: Option<TxRing>,
+    rx_provider: Option<Box<dyn RxBufferProvider>>,
+    auto_replenish: bool,
+    rx_waker: Option<Waker>,
+    /// RX/TX enable state saved by [`Igb::enter_low_power`].
+    low_power: Option<(bool, bool)>,
+    link_setup: LinkSetup,
 }
 
 impl Igb {
@@ -39,43 +100,571 @@ impl Igb {
         let mac = RefCell::new(mac::Mac::new(iobase));
         let phy = phy::Phy::new(mac.clone());
 
-        Ok(Self { mac, phy })
+        Ok(Self {
+            mac,
+            phy,
+            dma_mask: DmaMask::default(),
+            tx_watchdog: Default::default(),
+            reset_timeout: mac::DEFAULT_RESET_TIMEOUT,
+            autoneg_timeout: phy::DEFAULT_AUTONEG_TIMEOUT,
+            mtu: DEFAULT_MTU,
+            link_changes: 0,
+            link_up_at: None,
+            rx: None,
+            tx: None,
+            rx_provider: None,
+            auto_replenish: false,
+            rx_waker: None,
+            low_power: None,
+            link_setup: LinkSetup::default(),
+        })
     }
 
     pub fn open(&mut self) -> Result<(), DError> {
+        self.open_with(LinkSetup::AutoNeg)
+    }
+
+    /// Like [`Igb::open`] but brings the link up as described by `setup`.
+    pub fn open_with(&mut self, setup: LinkSetup) -> Result<(), DError> {
         // disable interrupts
         self.mac.borrow_mut().disable_interrupts();
+        // let firmware finish its own initialization first
+        self.mac
+            .borrow()
+            .wait_firmware_release(self.reset_timeout)?;
         // reset the device
         debug!("Resetting the device");
-        self.mac.borrow_mut().reset()?;
+        self.mac
+            .borrow_mut()
+            .reset_with_timeout(self.reset_timeout)?;
         // disable interrupts
         self.mac.borrow_mut().disable_interrupts();
+        self.link_changes = 0;
+        self.link_up_at = None;
+        self.link_setup = setup;
         // setup the phy and the link
         debug!("setting up PHY and link");
-        self.phy.power_up()?;
-        self.setup_phy_and_the_link()?;
-        // wait for auto-negotiation to complete
-        debug!("wait Auto-negotiation to complete");
-        self.phy.wait_for_auto_negotiation_complete()?;
+        self.setup_phy_and_the_link(setup)?;
+        if let LinkSetup::AutoNeg = setup {
+            // wait for auto-negotiation to complete
+            debug!("wait Auto-negotiation to complete");
+            self.phy.wait_auto_negotiation(self.autoneg_timeout)?;
+        }
         debug!("initialization complete");
         Ok(())
     }
 
-    fn setup_phy_and_the_link(&mut self) -> Result<(), DError> {
+    fn setup_phy_and_the_link(&mut self, setup: LinkSetup) -> Result<(), DError> {
         self.phy.power_up()?;
-        self.phy.enable_auto_negotiation()?;
+        match setup {
+            LinkSetup::AutoNeg => self.phy.enable_auto_negotiation()?,
+            LinkSetup::Forced { speed, full_duplex } => {
+                self.phy.force_speed_duplex(speed, full_duplex)?;
+                self.mac.borrow_mut().force_speed_duplex(speed, full_duplex);
+            }
+        }
 
         Ok(())
     }
 
+    /// Resets the device and applies the baseline register configuration of
+    /// [`mac::Mac::load_defaults`], giving the same starting point on every
+    /// board. Rings and filters have to be set up again afterwards.
+    pub fn load_defaults(&mut self) -> Result<(), DError> {
+        let mut mac = self.mac.borrow_mut();
+        mac.disable_interrupts();
+        mac.reset_with_timeout(self.reset_timeout)?;
+        mac.disable_interrupts();
+        mac.load_defaults();
+        Ok(())
+    }
+
+    pub const SUPPORTED_DEVICES: &'static [(u16, u16)] = device::SUPPORTED_DEVICES;
+
     pub fn check_vid_did(vid: u16, did: u16) -> bool {
-        // This is a placeholder for actual VID/DID checking logic.
-        // In a real implementation, this would check the device's
-        // vendor ID and device ID against the provided values.
-        vid == 0x8086 && [0x10C9, 0x1533].contains(&did)
+        Self::SUPPORTED_DEVICES.contains(&(vid, did))
+    }
+
+    /// See [`mac::Mac::set_verify_writes`]. Also covers PHY register writes,
+    /// see [`phy::Phy::set_verify_writes`].
+    pub fn set_verify_writes(&mut self, enable: bool) {
+        self.mac.borrow_mut().set_verify_writes(enable);
+        self.phy.set_verify_writes(enable);
+    }
+
+    /// Tells the driver which family and stepping it drives, e.g. from
+    /// [`Endpoint::device_kind`], so [`Igb::open`] applies its reset quirks.
+    pub fn set_device_kind(&mut self, kind: DeviceKind) {
+        self.mac.borrow_mut().set_device_kind(kind);
+    }
+
+    /// Sets how long [`Igb::open`] waits for the device reset to complete.
+    /// Some boards need more than the default of one second.
+    pub fn set_reset_timeout(&mut self, timeout: Duration) {
+        self.reset_timeout = timeout;
+    }
+
+    /// Sets how long [`Igb::open`] waits for the PHY to finish
+    /// auto-negotiation, three seconds by default.
+    pub fn set_autoneg_timeout(&mut self, timeout: Duration) {
+        self.autoneg_timeout = timeout;
+    }
+
+    /// Waits up to `timeout` for the MAC to report link up. Unlike the
+    /// auto-negotiation wait in [`Igb::open`], a timeout here fails with
+    /// [`DError::TimeoutAt`] naming STATUS.LU, telling the two phases apart.
+    pub fn wait_link_up(&mut self, timeout: Duration) -> Result<(), DError> {
+        self.mac.borrow().wait_link_up(timeout)?;
+        self.link_up_at.get_or_insert_with(kernel::now);
+        Ok(())
+    }
+
+    /// Forces `speed` and duplex on both MAC and PHY with auto-negotiation
+    /// off, for link partners that do not negotiate correctly. Gigabit needs
+    /// auto-negotiation and fails with [`DError::InvalidParameter`]. The
+    /// setting is kept across [`Igb::resume`].
+    pub fn force_link(&mut self, speed: Speed, full_duplex: bool) -> Result<(), DError> {
+        let setup = LinkSetup::Forced { speed, full_duplex };
+        self.setup_phy_and_the_link(setup)?;
+        self.link_setup = setup;
+        Ok(())
+    }
+
+    /// Like [`Igb::wait_link_up`], but returns the status the link came up
+    /// with.
+    pub fn wait_for_link(&mut self, timeout: Duration) -> Result<MacStatus, DError> {
+        self.wait_link_up(timeout)?;
+        Ok(self.status())
+    }
+
+    /// Restricts the bus addresses used for descriptor rings and packet
+    /// buffers. Memory outside the mask is rejected when it is handed to the
+    /// device, since the hardware would silently truncate the address.
+    /// Receive buffers from the provider that fall outside it are freed back
+    /// to the provider instead of being posted.
+    pub fn set_dma_mask(&mut self, mask: DmaMask) {
+        self.dma_mask = mask;
+    }
+
+    pub fn dma_mask(&self) -> DmaMask {
+        self.dma_mask
+    }
+
+    /// Watchdog for transmit hangs, meant to be called periodically (e.g.
+    /// once a second). Returns `true` when TDH has not moved for
+    /// [`tx::TX_HANG_CHECKS`] consecutive calls while descriptors are pending.
+    pub fn detect_tx_hang(&mut self) -> bool {
+        let (head, tail) = self.mac.borrow().tx_head_tail();
+        self.tx_watchdog.check(head, tail)
+    }
+
+    /// Returns whether every queued frame has left: the hardware head caught
+    /// up with the tail and the last submitted descriptor was written back.
+    /// Use it to drain the transmit path before stopping or resetting it.
+    pub fn tx_is_idle(&self) -> bool {
+        let (head, tail) = self.mac.borrow().tx_head_tail();
+        if head != tail {
+            return false;
+        }
+        self.tx
+            .as_ref()
+            .is_none_or(|ring| ring.last_written_back(tail as usize))
+    }
+
+    /// Resets the transmit unit after [`Igb::detect_tx_hang`] fired and
+    /// re-arms an empty ring. Frames that were pending are lost.
+    pub fn recover_tx_hang(&mut self) -> Result<(), DError> {
+        debug!("Recovering from TX hang");
+        self.mac.borrow_mut().reset_tx()?;
+        if let Some(ring) = self.tx.as_mut() {
+            ring.rewind();
+        }
+        self.tx_watchdog.reset();
+        Ok(())
+    }
+
+    /// Rate-limits interrupts of MSI-X `vector`, see
+    /// [`mac::Mac::set_interrupt_throttle`].
+    pub fn set_interrupt_throttle(
+        &mut self,
+        vector: usize,
+        interval_us: u32,
+    ) -> Result<(), DError> {
+        self.mac
+            .borrow_mut()
+            .set_interrupt_throttle(vector, interval_us)
+    }
+
+    /// Programs every interrupt vector and queue according to `preset`, see
+    /// [`ModerationPreset`] for the resulting register values.
+    pub fn set_interrupt_moderation(&mut self, preset: ModerationPreset) -> Result<(), DError> {
+        let mut mac = self.mac.borrow_mut();
+        for vector in 0..mac::VECTOR_COUNT {
+            mac.set_interrupt_throttle(vector, preset.interval_us())?;
+        }
+        for queue in 0..mac::QUEUE_COUNT {
+            mac.set_write_back_threshold(queue, preset.write_back_threshold())?;
+        }
+        Ok(())
+    }
+
+    /// Services pending legacy interrupt causes, call it from the interrupt
+    /// handler or a poll loop. Reading ICR acknowledges every cause, so the
+    /// snapshot is returned for further dispatch.
+    pub fn handle_interrupt(&mut self) -> mac::InterruptCauses {
+        let causes = self.mac.borrow_mut().take_interrupt_causes();
+        if causes.is_set(mac::ICR::LSC) {
+            self.link_changes = self.link_changes.wrapping_add(1);
+            let status = self.status();
+            if status.link_up {
+                self.mac
+                    .borrow_mut()
+                    .set_collision_distance_for_speed(status.speed, status.full_duplex);
+                self.link_up_at.get_or_insert_with(kernel::now);
+            } else {
+                self.link_up_at = None;
+            }
+        }
+        if causes.is_set(mac::ICR::RXDMT0) && self.auto_replenish {
+            self.replenish_rx();
+        }
+        if causes.is_set(mac::ICR::RXT0) || causes.is_set(mac::ICR::RXDMT0) {
+            if let Some(waker) = self.rx_waker.take() {
+                waker.wake();
+            }
+        }
+        causes
+    }
+
+    /// Services pending causes like [`Igb::handle_interrupt`] and returns the
+    /// new status if the link changed since the last call.
+    pub fn poll_link_change(&mut self) -> Option<MacStatus> {
+        let causes = self.handle_interrupt();
+        causes.is_set(mac::ICR::LSC).then(|| self.status())
+    }
+
+    /// Attaches `ring` to receive queue 0 and starts receiving: programs the
+    /// ring registers and the buffer size, RCTL.BSIZE for legacy rings or
+    /// SRRCTL.BSIZEPACKET for advanced ones, fills the ring from the buffer
+    /// provider if one is set, enables the queue and sets RCTL.RXEN.
+    ///
+    /// The ring must be within the DMA mask. Fails with
+    /// [`DError::InvalidParameter`] if a ring is already attached, see
+    /// [`Igb::teardown_rx`].
+    pub fn setup_rx(&mut self, mut ring: RxRing) -> Result<(), DError> {
+        if self.rx.is_some() || !self.dma_mask.contains(ring.region()) {
+            return Err(DError::InvalidParameter);
+        }
+
+        let mut mac = self.mac.borrow_mut();
+        mac.set_rx_enabled(false);
+        mac.set_rx_ring(0, &ring)?;
+        // Advanced rings got their buffer size through SRRCTL above.
+        if !ring.uses_advanced_descriptors() {
+            mac.set_rx_buffer_size(ring.buffer_size())?;
+        }
+        mac.start_rx_queue(0)?;
+        if let Some(provider) = self.rx_provider.as_mut() {
+            if let Some(tail) = ring.refill(provider.as_mut(), self.dma_mask) {
+                mac.set_rx_tail(0, tail);
+            }
+        }
+        mac.set_rx_enabled(true);
+        drop(mac);
+
+        self.rx = Some(ring);
+        Ok(())
+    }
+
+    /// Stops receiving and detaches the receive ring, handing its buffers
+    /// back to the buffer provider and freeing it, see [`RxRing::free`]. A
+    /// new ring can be attached with [`Igb::setup_rx`] afterwards.
+    ///
+    /// Fails with [`DError::InvalidParameter`] if no ring is attached.
+    pub fn teardown_rx(&mut self) -> Result<(), DError> {
+        let ring = self.rx.take().ok_or(DError::InvalidParameter)?;
+        let mut mac = self.mac.borrow_mut();
+        mac.set_rx_enabled(false);
+        match self.rx_provider.as_mut() {
+            Some(provider) => ring.free(&mut mac, 0, provider.as_mut()),
+            // Without a provider the ring never got any buffers.
+            None => ring.free(&mut mac, 0, &mut NoRxBuffers),
+        }
+    }
+
+    /// Gives the attached receive ring all new buffers from the buffer
+    /// provider, see [`RxRing::replenish_all`]. Fails with
+    /// [`DError::InvalidParameter`] without a ring or provider.
+    pub fn reset_rx_buffers(&mut self) -> Result<(), DError> {
+        let ring = self.rx.as_mut().ok_or(DError::InvalidParameter)?;
+        let provider = self.rx_provider.as_mut().ok_or(DError::InvalidParameter)?;
+        ring.replenish_all(
+            &mut self.mac.borrow_mut(),
+            0,
+            provider.as_mut(),
+            self.dma_mask,
+        )
+    }
+
+    /// Attaches `ring` to transmit queue 0 and starts the transmitter:
+    /// programs the ring registers and the TCTL collision parameters, enables
+    /// the queue and sets TCTL.EN. Attach a buffer with
+    /// [`TxRing::with_buffer`] to send through [`Igb::send`].
+    ///
+    /// The ring and its buffer must be within the DMA mask. Fails with
+    /// [`DError::InvalidParameter`] if a ring is already attached, see
+    /// [`Igb::teardown_tx`].
+    pub fn setup_tx(&mut self, ring: TxRing) -> Result<(), DError> {
+        if self.tx.is_some()
+            || !self.dma_mask.contains(ring.region())
+            || ring
+                .buffer()
+                .is_some_and(|buf| !self.dma_mask.contains(buf))
+        {
+            return Err(DError::InvalidParameter);
+        }
+
+        let mut mac = self.mac.borrow_mut();
+        mac.set_tx_enabled(false);
+        mac.set_tx_ring(0, &ring)?;
+        mac.init_tctl();
+        mac.start_tx_queue(0)?;
+        mac.set_tx_enabled(true);
+        drop(mac);
+
+        self.tx_watchdog.reset();
+        self.tx = Some(ring);
+        Ok(())
+    }
+
+    /// Stops the transmitter and detaches the transmit ring, freeing it and
+    /// its buffer, see [`TxRing::free`]. A new ring can be attached with
+    /// [`Igb::setup_tx`] afterwards.
+    ///
+    /// Fails with [`DError::InvalidParameter`] if no ring is attached.
+    pub fn teardown_tx(&mut self) -> Result<(), DError> {
+        let ring = self.tx.take().ok_or(DError::InvalidParameter)?;
+        let mut mac = self.mac.borrow_mut();
+        mac.set_tx_enabled(false);
+        ring.free(&mut mac, 0)
+    }
+
+    /// Sets the MTU, the largest payload of a frame. [`Igb::send`] rejects
+    /// frames above the resulting [`Igb::max_frame_size`]. Defaults to 1500.
+    pub fn set_mtu(&mut self, mtu: usize) {
+        self.mtu = mtu;
+    }
+
+    pub fn mtu(&self) -> usize {
+        self.mtu
+    }
+
+    /// Largest frame without FCS, which the hardware appends: the MTU plus
+    /// the Ethernet header and a VLAN tag.
+    pub fn max_frame_size(&self) -> usize {
+        self.mtu + ETH_HEADER_LEN + VLAN_TAG_LEN
+    }
+
+    /// Transmits `frame` and waits until the hardware wrote the descriptor
+    /// back. The frame is copied into the ring's buffer, so it may not be
+    /// larger than [`TxRing::buffer_size`], nor exceed
+    /// [`Igb::max_frame_size`]. After a timed out send the hardware may still
+    /// read the buffer, so this fails with [`DError::Busy`] until the earlier
+    /// frame was written back.
+    pub fn send(&mut self, frame: &[u8]) -> Result<(), DError> {
+        if frame.len() > self.max_frame_size() {
+            return Err(DError::InvalidParameter);
+        }
+        let ring = self.tx.as_mut().ok_or(DError::InvalidParameter)?;
+        let (idx, tail) = ring.push(frame)?;
+        self.mac.borrow_mut().set_tx_tail(0, tail);
+
+        wait_for(
+            "TX descriptor DD",
+            || ring.is_done(idx),
+            Duration::from_micros(10),
+            Some(TX_DONE_TRIES),
+        )?;
+        ring.reclaim();
+        Ok(())
+    }
+
+    /// Returns the next received frame without blocking, `None` if nothing
+    /// arrived. The frame borrows the DMA buffer, its descriptor is handed back
+    /// to the hardware by the following call.
+    pub fn recv(&mut self) -> Option<RxFrame<'_>> {
+        let ring = self.rx.as_mut()?;
+        if ring.release() {
+            let tail = match self.rx_provider.as_mut() {
+                Some(provider) => ring.refill(provider.as_mut(), self.dma_mask),
+                None => ring.recycle(),
+            };
+            if let Some(tail) = tail {
+                self.mac.borrow_mut().set_rx_tail(0, tail);
+            }
+        }
+        ring.poll()
+    }
+
+    /// Registers `waker` to be woken by [`Igb::handle_interrupt`] on the next
+    /// receive interrupt, for a task waiting on [`Igb::recv`]. The waker is
+    /// used once, register it again before waiting the next time.
+    pub fn set_rx_waker(&mut self, waker: Waker) {
+        self.rx_waker = Some(waker);
+    }
+
+    /// Sets the source of buffers used to refill the receive ring.
+    pub fn set_rx_buffer_provider(&mut self, provider: Box<dyn RxBufferProvider>) {
+        self.rx_provider = Some(provider);
+    }
+
+    /// With auto-replenish on, [`Igb::handle_interrupt`] refills the receive
+    /// ring from the buffer provider whenever the minimum threshold interrupt
+    /// (ICR.RXDMT0) fires, so the ring stays full under steady load. Frames are
+    /// still consumed through the normal receive path; refilling only touches
+    /// descriptors the driver has already handed back.
+    pub fn set_auto_replenish(&mut self, enable: bool) {
+        self.auto_replenish = enable;
+    }
+
+    /// Refills the receive ring from the buffer provider and returns how many
+    /// descriptors the hardware owns afterwards.
+    pub fn replenish_rx(&mut self) -> usize {
+        let (Some(ring), Some(provider)) = (self.rx.as_mut(), self.rx_provider.as_mut()) else {
+            return 0;
+        };
+        if let Some(tail) = ring.refill(provider.as_mut(), self.dma_mask) {
+            self.mac.borrow_mut().set_rx_tail(0, tail);
+        }
+        ring.desc_count() - 1 - ring.unused()
+    }
+
+    /// How long the link has been up, measured from the link-up transition
+    /// seen by [`Igb::handle_interrupt`] or [`Igb::wait_link_up`]. `None`
+    /// while the link is down. A short uptime next to a growing
+    /// [`Igb::link_change_count`] points at a flapping link.
+    pub fn link_up_since(&self) -> Option<Duration> {
+        self.link_up_at.map(|at| kernel::now().saturating_sub(at))
+    }
+
+    /// Number of link status changes seen by [`Igb::poll_link_change`] since
+    /// [`Igb::open`]. A growing count points at cabling or negotiation trouble.
+    pub fn link_change_count(&self) -> u32 {
+        self.link_changes
+    }
+
+    /// Idles the device between bursts: stops the receive and transmit units,
+    /// stops the MAC from mastering the bus so the PCIe link and DMA engines
+    /// can idle, and powers down the PHY, which drops the link. If any step
+    /// fails, the datapath is restored before returning the error.
+    ///
+    /// Register configuration, rings, filters and interrupt masks survive, and
+    /// the MAC stays powered and accessible so wake-up filters remain armed.
+    /// After [`Igb::resume`] the link renegotiates; frames that were in flight
+    /// when the datapath stopped are not sent.
+    pub fn enter_low_power(&mut self) -> Result<(), DError> {
+        if self.low_power.is_some() {
+            return Ok(());
+        }
+        let mut mac = self.mac.borrow_mut();
+        let (rx, tx) = (mac.rx_enabled(), mac.tx_enabled());
+        mac.set_rx_enabled(false);
+        mac.set_tx_enabled(false);
+        let res = mac.disable_bus_master();
+        drop(mac);
+
+        if let Err(e) = res.and_then(|_| self.phy.power_down()) {
+            let mut mac = self.mac.borrow_mut();
+            mac.enable_bus_master();
+            mac.set_rx_enabled(rx);
+            mac.set_tx_enabled(tx);
+            return Err(e);
+        }
+        self.low_power = Some((rx, tx));
+        Ok(())
+    }
+
+    /// Leaves the state entered by [`Igb::enter_low_power`]: lets the MAC
+    /// master the bus again, powers the PHY up, sets the link up as it was
+    /// opened and re-enables the datapath as it was.
+    pub fn resume(&mut self) -> Result<(), DError> {
+        let Some((rx, tx)) = self.low_power.take() else {
+            return Ok(());
+        };
+        self.mac.borrow_mut().enable_bus_master();
+        self.setup_phy_and_the_link(self.link_setup)?;
+
+        let mut mac = self.mac.borrow_mut();
+        mac.set_rx_enabled(rx);
+        mac.set_tx_enabled(tx);
+        Ok(())
+    }
+
+    /// Blinks the link LED for `duration` to locate the port, then restores
+    /// the previous LED configuration. Blocks for the whole duration.
+    pub fn identify(&mut self, duration: Duration) {
+        let saved = self.mac.borrow().led_control();
+        self.mac.borrow_mut().blink_led();
+        kernel::sleep(duration);
+        self.mac.borrow_mut().set_led_control(saved);
+    }
+
+    pub fn phy(&self) -> &phy::Phy {
+        &self.phy
+    }
+
+    /// Reads MII register `reg` of the internal PHY, e.g. BMSR (0x01) or the
+    /// PHY identifier (0x02/0x03).
+    pub fn phy_read(&self, reg: u32) -> Result<u16, DError> {
+        self.mac.borrow().read_mdic(self.phy.addr(), reg)
+    }
+
+    /// Writes MII register `reg` of the internal PHY.
+    pub fn phy_write(&self, reg: u32, val: u16) -> Result<(), DError> {
+        self.mac.borrow().write_mdic(self.phy.addr(), reg, val)
     }
 
     pub fn status(&self) -> MacStatus {
         self.mac.borrow().status()
     }
+
+    /// The permanent MAC address, see [`mac::Mac::read_mac_address`].
+    pub fn mac_address(&self) -> MacAddr6 {
+        self.mac.borrow().read_mac_address()
+    }
+
+    /// Loops transmitted frames back to the receiver inside the MAC, for a
+    /// self-test without cabling. See [`mac::Mac::set_loopback`].
+    ///
+    /// Enable it after [`Igb::open`] or [`Igb::load_defaults`], which reset
+    /// RCTL; [`Igb::setup_rx`] and [`Igb::setup_tx`] keep it, so they can
+    /// come before or after. Looped frames still pass the address filters.
+    /// Disabling restores a link forced with [`Igb::force_link`].
+    pub fn set_loopback(&mut self, enable: bool) {
+        let mut mac = self.mac.borrow_mut();
+        mac.set_loopback(enable);
+        if let (false, LinkSetup::Forced { speed, full_duplex }) = (enable, self.link_setup) {
+            mac.force_speed_duplex(speed, full_duplex);
+        }
+    }
+
+    /// Receives all frames regardless of destination address, see
+    /// [`mac::Mac::set_promiscuous`].
+    pub fn set_promiscuous(&mut self, enable: bool) {
+        self.mac.borrow_mut().set_promiscuous(enable);
+    }
+
+    pub fn is_promiscuous(&self) -> bool {
+        self.mac.borrow().is_promiscuous()
+    }
+
+    /// Reads and clears one statistics counter, see [`mac::Mac::read_stat`].
+    pub fn read_stat(&self, stat: StatId) -> u64 {
+        self.mac.borrow().read_stat(stat)
+    }
+
+    /// Per-queue counters of `queue`, see [`mac::Mac::queue_statistics`].
+    pub fn queue_statistics(&self, queue: usize) -> Result<QueueStatistics, DError> {
+        self.mac.borrow().queue_statistics(queue)
+    }
 }
@@ -1,19 +1,99 @@
-use crate::osal::*;
+use crate::{
+    osal::*,
+    rx::{RxRing, MAX_RX_BUFFER_SIZE},
+    tx::TxRing,
+    DeviceKind,
+};
 use core::fmt::Debug;
-use core::{ptr::NonNull, time::Duration};
-use log::error;
+use core::str::FromStr;
+use core::{net::Ipv4Addr, ptr::NonNull, time::Duration};
+use log::{error, warn};
 use mbarrier::mb;
 use tock_registers::registers::{ReadOnly, ReadWrite};
-use tock_registers::{interfaces::*, register_bitfields, register_structs, registers::*};
+use tock_registers::{
+    fields::{Field, FieldValue},
+    interfaces::*,
+    register_bitfields, register_structs, LocalRegisterCopy, RegisterLongName,
+};
+
+/// Time [`Mac::reset`] waits for the reset to complete.
+pub const DEFAULT_RESET_TIMEOUT: Duration = Duration::from_secs(1);
+/// Additional settle time after reset for steppings with
+/// [`DeviceKind::needs_extra_reset_delay`].
+pub const EXTRA_RESET_DELAY: Duration = Duration::from_millis(10);
 
 #[derive(Clone, Copy)]
 pub struct Mac {
     reg: NonNull<MacRegisters>,
+    verify_writes: bool,
+    kind: Option<DeviceKind>,
 }
 
 impl Mac {
     pub fn new(iobase: NonNull<u8>) -> Self {
-        Self { reg: iobase.cast() }
+        Self {
+            reg: iobase.cast(),
+            verify_writes: false,
+            kind: None,
+        }
+    }
+
+    /// Tells the MAC which family and stepping it drives, so resets can
+    /// apply the stepping's quirks, see [`DeviceKind::needs_extra_reset_delay`].
+    pub fn set_device_kind(&mut self, kind: DeviceKind) {
+        self.kind = Some(kind);
+    }
+
+    pub fn device_kind(&self) -> Option<DeviceKind> {
+        self.kind
+    }
+
+    /// Reads back every configuration register write and logs a warning on
+    /// mismatch, which points at a wrong BAR mapping or decoding being off
+    /// during bring-up. Self-clearing and write-1-to-clear registers (CTRL.RST,
+    /// MDIC, EERD, ICR/EICR, WUS), the set/clear interrupt mask registers,
+    /// the SWSM/SW_FW_SYNC semaphores and the ring head/tail and queue enable
+    /// writes of the datapath are never verified. Off by default since every
+    /// checked write costs an extra MMIO read.
+    ///
+    /// The flag is part of this `Mac` value, copies taken earlier (such as
+    /// the one inside [`crate::phy::Phy`]) keep their own setting.
+    pub fn set_verify_writes(&mut self, enable: bool) {
+        self.verify_writes = enable;
+    }
+
+    pub fn verify_writes(&self) -> bool {
+        self.verify_writes
+    }
+
+    /// Writes `value` to `reg`, verifying the bits in `mask` if enabled.
+    fn write_checked<R: RegisterLongName>(
+        &self,
+        name: &str,
+        reg: &ReadWrite<u32, R>,
+        value: u32,
+        mask: u32,
+    ) {
+        reg.set(value);
+        if self.verify_writes {
+            let read = reg.get();
+            if (read ^ value) & mask != 0 {
+                warn!(
+                    "{} write not applied: wrote {:#010x}, read back {:#010x}",
+                    name, value, read
+                );
+            }
+        }
+    }
+
+    /// Read-modify-write of `reg`, verifying the result if enabled.
+    fn modify_checked<R: RegisterLongName>(
+        &self,
+        name: &str,
+        reg: &ReadWrite<u32, R>,
+        field: FieldValue<u32, R>,
+    ) {
+        self.write_checked(name, reg, field.modify(reg.get()), u32::MAX);
     }
 
     pub fn iobase(&self) -> NonNull<u8> {
@@ -47,6 +127,18 @@ impl Mac {
         }
     }
 
+    /// Waits up to `timeout` for STATUS.LU, the MAC side of link up that may
+    /// lag behind the PHY finishing auto-negotiation.
+    pub fn wait_link_up(&self, timeout: Duration) -> Result<(), DError> {
+        let interval = Duration::from_millis(10);
+        wait_for(
+            "STATUS.LU",
+            || self.reg().status.is_set(STATUS::LU),
+            interval,
+            Some((timeout.as_millis() / interval.as_millis()).max(1) as usize),
+        )
+    }
+
     pub fn write_mdic(&self, phys_addr: u32, offset: u32, data: u16) -> Result<(), DError> {
         self.reg().mdic.write(
             MDIC::REGADDR.val(offset)
@@ -56,18 +148,7 @@ impl Mac {
         );
         mb();
 
-        loop {
-            let mdic = self.reg().mdic.extract();
-
-            if mdic.is_set(MDIC::READY) {
-                break;
-            }
-            if mdic.is_set(MDIC::E) {
-                error!("MDIC read error");
-                return Err(DError::Unknown("MDIC read error"));
-            }
-        }
-
+        self.wait_mdic("MDIC write error")?;
         Ok(())
     }
 
@@ -76,16 +157,148 @@ impl Mac {
             .mdic
             .write(MDIC::REGADDR.val(offset) + MDIC::PHY_ADDR.val(phys_addr) + MDIC::OP::Read);
         mb();
-        loop {
-            let mdic = self.reg().mdic.extract();
-            if mdic.is_set(MDIC::READY) {
-                return Ok(mdic.read(MDIC::DATA) as _);
-            }
-            if mdic.is_set(MDIC::E) {
-                error!("MDIC read error");
-                return Err(DError::Unknown("MDIC read error"));
-            }
+
+        let mdic = self.wait_mdic("MDIC read error")?;
+        Ok(mdic.read(MDIC::DATA) as _)
+    }
+
+    /// Takes the software side of the PHY 0 semaphore shared with firmware,
+    /// so a sequence of PHY accesses is not interleaved with the management
+    /// firmware's. Pair with [`Mac::release_phy`].
+    pub fn acquire_phy(&self) -> Result<(), DError> {
+        self.acquire_swfw_sync(
+            "SW_FW_SYNC.SW_PHY_SM0",
+            SW_FW_SYNC::SW_PHY_SM0,
+            SW_FW_SYNC::FW_PHY_SM0,
+        )
+    }
+
+    /// Releases the semaphore taken by [`Mac::acquire_phy`].
+    pub fn release_phy(&self) {
+        self.release_swfw_sync(SW_FW_SYNC::SW_PHY_SM0);
+    }
+
+    /// Reads one 16-bit word from the NVM through EERD, holding the EEPROM
+    /// semaphore so firmware does not access the NVM at the same time. Words
+    /// 0 to 2 hold the factory MAC address.
+    pub fn read_nvm_word(&self, addr: u16) -> Result<u16, DError> {
+        if addr > EERD::ADDR.mask as u16 {
+            return Err(DError::InvalidParameter);
+        }
+        self.acquire_swfw_sync(
+            "SW_FW_SYNC.SW_EEP_SM",
+            SW_FW_SYNC::SW_EEP_SM,
+            SW_FW_SYNC::FW_EEP_SM,
+        )?;
+
+        self.reg()
+            .eerd
+            .write(EERD::ADDR.val(addr as _) + EERD::START::SET);
+        let res = wait_for(
+            "EERD.DONE",
+            || self.reg().eerd.is_set(EERD::DONE),
+            EERD_POLL_INTERVAL,
+            Some(EERD_POLL_COUNT),
+        )
+        .map(|_| self.reg().eerd.read(EERD::DATA) as u16);
+
+        self.release_swfw_sync(SW_FW_SYNC::SW_EEP_SM);
+        res
+    }
+
+    /// Sets the software bit `sw` in SW_FW_SYNC once neither it nor the
+    /// firmware bit `fw` of the same resource is held.
+    fn acquire_swfw_sync(
+        &self,
+        what: &'static str,
+        sw: Field<u32, SW_FW_SYNC::Register>,
+        fw: Field<u32, SW_FW_SYNC::Register>,
+    ) -> Result<(), DError> {
+        let mut owned = false;
+        wait_for(
+            what,
+            || {
+                if self.get_hw_semaphore().is_err() {
+                    return false;
+                }
+                let sync = self.reg().sw_fw_sync.extract();
+                if !sync.is_set(sw) && !sync.is_set(fw) {
+                    self.reg().sw_fw_sync.set(sync.get() | sw.val(1).value);
+                    owned = true;
+                }
+                self.put_hw_semaphore();
+                owned
+            },
+            SWFW_SYNC_INTERVAL,
+            Some(SWFW_SYNC_TRIES),
+        )
+    }
+
+    /// Clears the software bit `sw` taken by `acquire_swfw_sync`.
+    fn release_swfw_sync(&self, sw: Field<u32, SW_FW_SYNC::Register>) {
+        // Clearing our bit should not race a firmware update of the register,
+        // but holding on to the resource forever would be worse.
+        if self.get_hw_semaphore().is_err() {
+            warn!("SWSM semaphore stuck, releasing SW_FW_SYNC without it");
+        }
+        self.reg().sw_fw_sync.modify(sw.val(0));
+        self.put_hw_semaphore();
+    }
+
+    /// Takes the hardware semaphore guarding SW_FW_SYNC: SMBI against other
+    /// software agents, then SWESMBI against firmware.
+    fn get_hw_semaphore(&self) -> Result<(), DError> {
+        let swsm = &self.reg().swsm;
+        // Reading SWSM sets SMBI, we own it if it read back clear.
+        wait_for(
+            "SWSM.SMBI",
+            || !swsm.is_set(SWSM::SMBI),
+            Duration::from_micros(50),
+            Some(HW_SEMAPHORE_TRIES),
+        )?;
+        let res = wait_for(
+            "SWSM.SWESMBI",
+            || {
+                swsm.modify(SWSM::SWESMBI::SET);
+                swsm.is_set(SWSM::SWESMBI)
+            },
+            Duration::from_micros(50),
+            Some(HW_SEMAPHORE_TRIES),
+        );
+        if res.is_err() {
+            self.put_hw_semaphore();
         }
+        res
+    }
+
+    fn put_hw_semaphore(&self) {
+        self.reg()
+            .swsm
+            .modify(SWSM::SMBI::CLEAR + SWSM::SWESMBI::CLEAR);
+    }
+
+    /// Waits for the pending MDIC transaction, failing with `error` if the PHY
+    /// reported one.
+    fn wait_mdic(
+        &self,
+        error: &'static str,
+    ) -> Result<LocalRegisterCopy<u32, MDIC::Register>, DError> {
+        let mut mdic = self.reg().mdic.extract();
+        wait_for(
+            "MDIC READY",
+            || {
+                mdic = self.reg().mdic.extract();
+                mdic.is_set(MDIC::READY) || mdic.is_set(MDIC::E)
+            },
+            MDIC_POLL_INTERVAL,
+            Some(MDIC_POLL_COUNT),
+        )?;
+
+        if mdic.is_set(MDIC::E) {
+            error!("{}", error);
+            return Err(DError::Unknown(error));
+        }
+        Ok(mdic)
     }
 
     pub fn disable_interrupts(&mut self) {
@@ -93,15 +306,360 @@ impl Mac {
         self.clear_interrupts();
     }
 
+    /// Masks all interrupts until the returned guard is dropped, which
+    /// restores exactly the IMS/EIMS masks that were set before. Causes
+    /// raised in between stay pending, nothing is acknowledged.
+    pub fn mask_all_scoped(&mut self) -> InterruptGuard<'_> {
+        let reg = self.reg();
+        let ims = reg.ims.get();
+        let eims = reg.eims.get();
+        reg.imc.set(u32::MAX);
+        reg.eimc.set(u32::MAX);
+        InterruptGuard {
+            mac: self,
+            ims,
+            eims,
+        }
+    }
+
+    /// Coarse default that unmasks every extended cause (EIMS). Prefer
+    /// [`Mac::enable_interrupt_causes`] to pick the causes actually handled.
     pub fn enable_interrupts(&mut self) {
         self.reg_mut().eims.set(u32::MAX);
     }
+
+    /// Unmasks the legacy causes in `mask` through IMS, leaving the others as
+    /// they are. The EICR "other" cause they are reported through is
+    /// unmasked as well.
+    pub fn enable_interrupt_causes(&mut self, mask: InterruptMask) {
+        let reg = self.reg();
+        reg.ims.set(mask.bits());
+        reg.eims.set(EICR_OTHER);
+    }
+
+    /// Masks the legacy causes in `mask` through IMC.
+    pub fn disable_interrupt_causes(&mut self, mask: InterruptMask) {
+        self.reg().imc.set(mask.bits());
+    }
     pub fn clear_interrupts(&mut self) {
         // Clear interrupt mask
         self.reg_mut().eimc.set(u32::MAX);
         self.reg().eicr.get();
     }
 
+    /// Returns the pending causes if this device is asserting the legacy
+    /// interrupt line, `None` if the interrupt belongs to another device.
+    ///
+    /// Meant for shared INTx handlers. With `GPIE.NSICR` cleared, reading ICR
+    /// only clears it when the device actually asserted the interrupt, so a
+    /// handler can bail out early without touching causes it does not own.
+    /// When it is ours the causes are consumed by this read and have to be
+    /// handled from the returned copy.
+    pub fn interrupt_is_ours(&self) -> Option<InterruptCauses> {
+        let icr = self.reg().icr.extract();
+        icr.is_set(ICR::INT_ASSERTED).then_some(icr)
+    }
+
+    /// Chooses whether frames failing the CRC check are stored (RCTL.SBP) for
+    /// diagnostics or dropped by the hardware, which is the normal setting.
+    /// Stored frames are flagged through [`crate::RxDescriptor::crc_error`].
+    pub fn set_store_bad_packets(&mut self, store: bool) {
+        let sbp = if store {
+            RCTL::SBP::Store
+        } else {
+            RCTL::SBP::DoNotStore
+        };
+        self.modify_checked("RCTL", &self.reg().rctl, sbp);
+    }
+
+    pub fn rx_enabled(&self) -> bool {
+        self.reg().rctl.is_set(RCTL::RXEN)
+    }
+
+    pub fn set_rx_enabled(&mut self, enable: bool) {
+        let rxen = if enable {
+            RCTL::RXEN::Enabled
+        } else {
+            RCTL::RXEN::Disabled
+        };
+        self.modify_checked("RCTL", &self.reg().rctl, rxen);
+    }
+
+    pub fn tx_enabled(&self) -> bool {
+        self.reg().tctl.is_set(TCTL::EN)
+    }
+
+    pub fn set_tx_enabled(&mut self, enable: bool) {
+        let en = if enable {
+            TCTL::EN::Enabled
+        } else {
+            TCTL::EN::Disabled
+        };
+        self.modify_checked("TCTL", &self.reg().tctl, en);
+    }
+
+    /// Stops the MAC from issuing DMA requests (CTRL.GIO_MASTER_DISABLE) and
+    /// waits for the outstanding ones to complete, letting the PCIe link and
+    /// the DMA engines idle. Registers stay accessible.
+    pub fn disable_bus_master(&mut self) -> Result<(), DError> {
+        self.modify_checked("CTRL", &self.reg().ctrl, CTRL::GIO_MASTER_DISABLE::SET);
+        wait_for(
+            "STATUS.GIO_MASTER_ENABLE clear",
+            || !self.reg().status.is_set(STATUS::GIO_MASTER_ENABLE),
+            Duration::from_micros(100),
+            Some(GIO_MASTER_TRIES),
+        )
+    }
+
+    /// Lets the MAC issue DMA requests again after
+    /// [`Mac::disable_bus_master`].
+    pub fn enable_bus_master(&mut self) {
+        self.modify_checked("CTRL", &self.reg().ctrl, CTRL::GIO_MASTER_DISABLE::CLEAR);
+    }
+
+    pub fn led_control(&self) -> u32 {
+        self.reg().ledctl.get()
+    }
+
+    pub fn set_led_control(&mut self, value: u32) {
+        self.write_checked("LEDCTL", &self.reg().ledctl, value, u32::MAX);
+    }
+
+    /// Makes LED0 (the link LED) blink, keeping the other LEDs as they are.
+    pub fn blink_led(&mut self) {
+        self.modify_checked(
+            "LEDCTL",
+            &self.reg().ledctl,
+            LEDCTL::LED0_MODE::On + LEDCTL::LED0_BLINK::SET,
+        );
+    }
+
+    /// Reports the kind and size of the attached NVM from EEC.
+    pub fn nvm_info(&self) -> NvmInfo {
+        let eec = self.reg().eec.extract();
+        let kind = if eec.is_set(EEC::FLASH_DETECTED) {
+            NvmType::Flash
+        } else if eec.is_set(EEC::EE_PRES) {
+            NvmType::Eeprom
+        } else {
+            NvmType::Invm
+        };
+        // Same clamp as the reference driver, the field saturates at 32K words.
+        let size_words = 1 << (eec.read(EEC::EE_SIZE) + 6).min(15);
+
+        NvmInfo {
+            kind,
+            size_words,
+            auto_read_done: eec.is_set(EEC::AUTO_RD),
+        }
+    }
+
+    /// Reads back the interrupt behavior configured in GPIE.
+    pub fn gpie_config(&self) -> GpieConfig {
+        GpieConfig::decode(self.reg().gpie.get())
+    }
+
+    /// Sets GPIE.EIAME: when enabled, the EIMS bits selected with
+    /// [`Mac::set_auto_mask_vectors`] are cleared as their interrupt asserts,
+    /// so a handler does not have to mask the vector itself.
+    pub fn set_auto_mask_on_assert(&mut self, enabled: bool) {
+        let eiame = if enabled {
+            GPIE::EIAME::Enabled
+        } else {
+            GPIE::EIAME::Disabled
+        };
+        self.modify_checked("GPIE", &self.reg().gpie, eiame);
+    }
+
+    /// Selects the extended interrupt causes (EIAM bits) that are auto-masked
+    /// on assertion while GPIE.EIAME is set.
+    pub fn set_auto_mask_vectors(&mut self, mask: u32) {
+        self.write_checked("EIAM", &self.reg().eiam, mask, u32::MAX);
+    }
+
+    /// Programs TCTL.COLD for the resolved link: 0x40 byte times for full
+    /// duplex (gigabit), 0x200 for half duplex 10/100 links, where a too short
+    /// distance causes late collisions.
+    pub fn set_collision_distance_for_speed(&mut self, speed: Speed, full_duplex: bool) {
+        let cold = if full_duplex || speed == Speed::Mb1000 {
+            COLLISION_DISTANCE_FD
+        } else {
+            COLLISION_DISTANCE_HD
+        };
+        self.modify_checked("TCTL", &self.reg().tctl, TCTL::COLD.val(cold));
+    }
+
+    /// Forces the MAC to `speed` and duplex (CTRL.FRCSPD/FRCDPLX) instead of
+    /// taking them from the PHY's resolved link.
+    pub fn force_speed_duplex(&mut self, speed: Speed, full_duplex: bool) {
+        let speed = match speed {
+            Speed::Mb1000 => CTRL::SPEED::Speed1000,
+            Speed::Mb100 => CTRL::SPEED::Speed100,
+            Speed::Mb10 => CTRL::SPEED::Speed10,
+        };
+        self.modify_checked(
+            "CTRL",
+            &self.reg().ctrl,
+            CTRL::FRCSPD::SET + CTRL::FRCDPLX::SET + speed + CTRL::FD.val(full_duplex as _),
+        );
+    }
+
+    /// Switches RCTL.LBM between MAC loopback and normal operation. Loopback
+    /// needs a link, so enabling also sets CTRL.SLU and forces 1000 Mb/s full
+    /// duplex; disabling releases the forced speed and duplex again.
+    pub fn set_loopback(&mut self, enable: bool) {
+        if enable {
+            self.force_speed_duplex(Speed::Mb1000, true);
+            self.modify_checked("CTRL", &self.reg().ctrl, CTRL::SLU::SET);
+            self.modify_checked("RCTL", &self.reg().rctl, RCTL::LBM::MacLoopback);
+        } else {
+            self.modify_checked("RCTL", &self.reg().rctl, RCTL::LBM::Normal);
+            self.modify_checked(
+                "CTRL",
+                &self.reg().ctrl,
+                CTRL::FRCSPD::CLEAR + CTRL::FRCDPLX::CLEAR,
+            );
+        }
+    }
+
+    /// Returns the RAL/RAH register pair of receive address filter `index`.
+    fn rar(&self, index: usize) -> Option<(&ReadWrite<u32>, &ReadWrite<u32>)> {
+        let (table, i) = match index {
+            0..16 => (&self.reg().ralh_0_15, index),
+            16..RAR_COUNT => (&self.reg().ralh_16_23, index - 16),
+            _ => return None,
+        };
+        Some((&table[i * 2], &table[i * 2 + 1]))
+    }
+
+    /// Reads receive address filter 0, which holds the permanent station
+    /// address the hardware loads from the NVM at reset.
+    pub fn read_mac_address(&self) -> MacAddr6 {
+        let (ral, rah) = self.rar(0).unwrap();
+        let low = ral.get().to_le_bytes();
+        let high = (rah.get() & RAH_ADDR_MASK).to_le_bytes();
+        MacAddr6::new([low[0], low[1], low[2], low[3], high[0], high[1]])
+    }
+
+    /// Programs receive address filter `index` (0 to 23) with `addr` and
+    /// marks it valid. Index 0 overrides the station address, the others add
+    /// secondary unicast addresses. With `queue` set, frames to `addr` are
+    /// steered to that receive queue (RAH.QSEL). Only the i210 has QSEL, the
+    /// same bits select VMDq pools on the 82576, so steering fails there with
+    /// [`DError::InvalidParameter`].
+    pub fn set_mac_address(
+        &mut self,
+        kind: DeviceKind,
+        index: usize,
+        addr: MacAddr6,
+        queue: Option<usize>,
+    ) -> Result<(), DError> {
+        let (ral, rah) = self.rar(index).ok_or(DError::InvalidParameter)?;
+        let b = addr.bytes();
+        let low = u32::from_le_bytes([b[0], b[1], b[2], b[3]]);
+        let mut high = u32::from_le_bytes([b[4], b[5], 0, 0]);
+        if let Some(queue) = queue {
+            if queue >= QUEUE_COUNT || !matches!(kind, DeviceKind::I210 { .. }) {
+                return Err(DError::InvalidParameter);
+            }
+            high |= RAH_QSEL_ENABLE | (queue as u32) << RAH_QSEL_SHIFT;
+        }
+        // Invalidate first so the filter never matches a half written address.
+        self.write_checked("RAH", rah, 0, u32::MAX);
+        self.write_checked("RAL", ral, low, u32::MAX);
+        self.write_checked("RAH", rah, high | RAH_AV, u32::MAX);
+        Ok(())
+    }
+
+    /// Receive queue filter `index` steers to, `None` if it does not steer,
+    /// `index` is out of range or the device has no QSEL (82576).
+    pub fn mac_address_queue(&self, kind: DeviceKind, index: usize) -> Option<usize> {
+        if !matches!(kind, DeviceKind::I210 { .. }) {
+            return None;
+        }
+        let (_, rah) = self.rar(index)?;
+        let high = rah.get();
+        (high & RAH_QSEL_ENABLE != 0).then_some(((high & RAH_QSEL_MASK) >> RAH_QSEL_SHIFT) as usize)
+    }
+
+    /// Counts receive address filters with the address valid (AV) bit set.
+    pub fn mac_address_filter_count(&self) -> usize {
+        (0..RAR_COUNT)
+            .filter_map(|i| self.rar(i))
+            .filter(|(_, rah)| rah.get() & RAH_AV != 0)
+            .count()
+    }
+
+    /// Turns unicast and multicast promiscuous mode (RCTL.UPE/MPE) on or
+    /// off. Enabling also accepts broadcasts (RCTL.BAM); disabling leaves
+    /// BAM set, as ARP needs it in normal operation too.
+    pub fn set_promiscuous(&mut self, enable: bool) {
+        let mode = if enable {
+            RCTL::UPE::Enabled + RCTL::MPE::Enabled + RCTL::BAM::Accept
+        } else {
+            RCTL::UPE::Disabled + RCTL::MPE::Disabled
+        };
+        self.modify_checked("RCTL", &self.reg().rctl, mode);
+    }
+
+    /// Whether both unicast and multicast promiscuous mode are on.
+    pub fn is_promiscuous(&self) -> bool {
+        let rctl = self.reg().rctl.extract();
+        rctl.is_set(RCTL::UPE) && rctl.is_set(RCTL::MPE)
+    }
+
+    /// Replaces the multicast table with the hashes of `addrs`, so the MAC
+    /// accepts frames sent to any of them. The 12-bit hash is taken from the
+    /// address bits selected by the current RCTL.MO setting.
+    pub fn set_multicast_filter(&mut self, addrs: &[MacAddr6]) {
+        let mut table = [0u32; MTA_COUNT];
+        let mo = self.reg().rctl.read(RCTL::MO);
+        for addr in addrs {
+            let hash = multicast_hash(addr, mo);
+            table[hash >> 5] |= 1 << (hash & 0x1f);
+        }
+        for (reg, value) in self.reg().mta.iter().zip(table) {
+            self.write_checked("MTA", reg, value, u32::MAX);
+        }
+    }
+
+    /// Zeroes the multicast table so no multicast frames pass the hash filter.
+    pub fn clear_multicast_filter(&mut self) {
+        self.set_multicast_filter(&[]);
+    }
+
+    /// Reads and acknowledges the pending legacy interrupt causes (ICR is
+    /// read-to-clear).
+    pub fn take_interrupt_causes(&mut self) -> InterruptCauses {
+        self.reg().icr.extract()
+    }
+
+    /// Reads and acknowledges ICR like [`Mac::take_interrupt_causes`], decoded
+    /// into an [`InterruptSummary`].
+    pub fn read_interrupt_cause(&mut self) -> InterruptSummary {
+        self.take_interrupt_causes().into()
+    }
+
+    /// Raises `cause` from software through EICS, setting the matching EICR
+    /// bit as if the event happened, to exercise the interrupt path without
+    /// traffic.
+    pub fn trigger_interrupt(&mut self, cause: InterruptCause) -> Result<(), DError> {
+        let bit = match cause {
+            InterruptCause::Vector(n) if (n as usize) < VECTOR_COUNT => 1 << n,
+            InterruptCause::Vector(_) => return Err(DError::InvalidParameter),
+            InterruptCause::TcpTimer => EICR_TCP_TIMER,
+            InterruptCause::Other => EICR_OTHER,
+        };
+        self.reg().eics.set(bit);
+        Ok(())
+    }
+
+    /// Hands receive descriptors up to (excluding) `tail` to the hardware.
+    pub fn set_rx_tail(&mut self, queue: usize, tail: u32) {
+        mb();
+        self.reg().rx_queues[queue].rdt.set(tail);
+    }
+
     pub fn link_mode(&self) -> Option<LinkMode> {
         Some(
             match self.reg().ctrl_ext.read_as_enum(CTRL_EXT::LINK_MODE) {
@@ -114,18 +672,668 @@ impl Mac {
     }
 
     pub fn reset(&mut self) -> Result<(), DError> {
+        self.reset_with_timeout(DEFAULT_RESET_TIMEOUT)
+    }
+
+    /// Whether manageability firmware is present but has not finished its own
+    /// initialization (FWSM.FW_MODE set, FWSM.FW_VAL clear). Resetting or
+    /// configuring the device in this window races with the firmware.
+    pub fn is_firmware_holding_reset(&self) -> bool {
+        let fwsm = self.reg().fwsm.extract();
+        !fwsm.matches_all(FWSM::FW_MODE::None) && !fwsm.is_set(FWSM::FW_VAL)
+    }
+
+    /// Waits up to `timeout` for firmware to release the device, see
+    /// [`Mac::is_firmware_holding_reset`].
+    pub fn wait_firmware_release(&self, timeout: Duration) -> Result<(), DError> {
+        let interval = Duration::from_millis(1);
+        wait_for(
+            "FWSM.FW_VAL",
+            || !self.is_firmware_holding_reset(),
+            interval,
+            Some(timeout.as_millis().max(1) as usize),
+        )
+    }
+
+    /// Resets MAC and PHY, waiting up to `timeout` each for CTRL.RST to
+    /// self-clear and for the NVM auto-read to finish. Steppings that need it
+    /// get an extra [`EXTRA_RESET_DELAY`] before the NVM check.
+    pub fn reset_with_timeout(&mut self, timeout: Duration) -> Result<(), DError> {
+        let interval = Duration::from_millis(1);
         self.reg_mut()
             .ctrl
             .modify(CTRL::RST::Reset + CTRL::PHY_RST::SET);
         wait_for(
+            "CTRL.RST",
             || self.reg().ctrl.matches_any(&[CTRL::RST::Normal]),
+            interval,
+            Some(timeout.as_millis().max(1) as usize),
+        )?;
+        if self.kind.is_some_and(|kind| kind.needs_extra_reset_delay()) {
+            kernel::sleep(EXTRA_RESET_DELAY);
+        }
+        self.wait_nvm_autoread(timeout)
+    }
+
+    /// Waits up to `timeout` for EEC.AUTO_RD, set once the hardware loaded
+    /// the NVM after a reset. Until then the MAC address and other
+    /// NVM-backed registers read garbage.
+    pub fn wait_nvm_autoread(&self, timeout: Duration) -> Result<(), DError> {
+        let interval = Duration::from_millis(1);
+        wait_for(
+            "EEC.AUTO_RD",
+            || self.reg().eec.is_set(EEC::AUTO_RD),
+            interval,
+            Some(timeout.as_millis().max(1) as usize),
+        )
+    }
+
+    /// Writes the baseline configuration drivers apply after a reset, so the
+    /// device starts the same regardless of what firmware left behind:
+    ///
+    /// | Register | Value |
+    /// |----------|-------|
+    /// | RCTL | receiver disabled, broadcast accept, 2048 byte buffers |
+    /// | TCTL | transmitter disabled, PSP, CT 0x0F, COLD 0x40, RTLC |
+    /// | TIPG | IPGT 8, IPGR1 8, IPGR2 6 |
+    /// | RXDCTL | PTHRESH 8, HTHRESH 8, WTHRESH 4 |
+    /// | TXDCTL | PTHRESH 8, HTHRESH 1, WTHRESH 16 |
+    ///
+    /// The queue enable bits in RXDCTL/TXDCTL are left as they are.
+    pub fn load_defaults(&mut self) {
+        let reg = self.reg();
+        self.write_checked(
+            "RCTL",
+            &reg.rctl,
+            (RCTL::BAM::Accept + RCTL::BSIZE::Bytes2048).value,
+            u32::MAX,
+        );
+        self.write_checked(
+            "TCTL",
+            &reg.tctl,
+            (TCTL::PSP::Enabled
+                + TCTL::CT.val(0x0F)
+                + TCTL::COLD.val(COLLISION_DISTANCE_FD)
+                + TCTL::RTLC::SET)
+                .value,
+            u32::MAX,
+        );
+        self.write_checked(
+            "TIPG",
+            &reg.tipg,
+            (TIPG::IPGT.val(8) + TIPG::IPGR1.val(8) + TIPG::IPGR2.val(6)).value,
+            u32::MAX,
+        );
+        for queue in 0..QUEUE_COUNT {
+            let rx = &reg.rx_queues[queue];
+            let tx = &reg.tx_queues[queue];
+            self.modify_checked(
+                "RXDCTL",
+                &rx.rxdctl,
+                RXDCTL::PTHRESH.val(RX_PTHRESH)
+                    + RXDCTL::HTHRESH.val(RX_HTHRESH)
+                    + RXDCTL::WTHRESH.val(4),
+            );
+            self.modify_checked(
+                "TXDCTL",
+                &tx.txdctl,
+                TXDCTL::PTHRESH.val(TX_PTHRESH)
+                    + TXDCTL::HTHRESH.val(TX_HTHRESH)
+                    + TXDCTL::WTHRESH.val(16),
+            );
+        }
+    }
+
+    /// Turns descriptor prefetching on all queues on or off by programming
+    /// the prefetch and host thresholds of RXDCTL/TXDCTL, either to the
+    /// [`Mac::load_defaults`] values or to zero.
+    ///
+    /// Meant for chasing descriptor coherency or barrier bugs. Without
+    /// prefetch the device fetches descriptors one at a time, costing an
+    /// extra PCIe round trip per packet and noticeably lowering throughput.
+    pub fn set_descriptor_prefetch(&mut self, enabled: bool) {
+        let reg = self.reg();
+        let (rx_p, rx_h, tx_p, tx_h) = if enabled {
+            (RX_PTHRESH, RX_HTHRESH, TX_PTHRESH, TX_HTHRESH)
+        } else {
+            (0, 0, 0, 0)
+        };
+        for queue in 0..QUEUE_COUNT {
+            self.modify_checked(
+                "RXDCTL",
+                &reg.rx_queues[queue].rxdctl,
+                RXDCTL::PTHRESH.val(rx_p) + RXDCTL::HTHRESH.val(rx_h),
+            );
+            self.modify_checked(
+                "TXDCTL",
+                &reg.tx_queues[queue].txdctl,
+                TXDCTL::PTHRESH.val(tx_p) + TXDCTL::HTHRESH.val(tx_h),
+            );
+        }
+    }
+
+    /// Sets the minimum interval between interrupts of MSI-X `vector`.
+    /// 0 disables throttling, i.e. one interrupt per event.
+    pub fn set_interrupt_throttle(
+        &mut self,
+        vector: usize,
+        interval_us: u32,
+    ) -> Result<(), DError> {
+        let eitr = self
+            .reg()
+            .eitr
+            .get(vector)
+            .ok_or(DError::InvalidParameter)?;
+        if interval_us > EITR::INTERVAL.mask {
+            return Err(DError::InvalidParameter);
+        }
+        // CNT_IGNR only affects the write itself and is not read back.
+        self.write_checked(
+            "EITR",
+            eitr,
+            (EITR::INTERVAL.val(interval_us) + EITR::CNT_IGNR::SET).value,
+            EITR::INTERVAL.mask << EITR::INTERVAL.shift,
+        );
+        Ok(())
+    }
+
+    /// Sets the descriptor write-back threshold (WTHRESH) of RX and TX `queue`.
+    /// The hardware writes back completed descriptors in batches of this size.
+    pub fn set_write_back_threshold(&mut self, queue: usize, wthresh: u32) -> Result<(), DError> {
+        let reg = self.reg();
+        let (Some(rx), Some(tx)) = (reg.rx_queues.get(queue), reg.tx_queues.get(queue)) else {
+            return Err(DError::InvalidParameter);
+        };
+        if wthresh > RXDCTL::WTHRESH.mask {
+            return Err(DError::InvalidParameter);
+        }
+        self.modify_checked("RXDCTL", &rx.rxdctl, RXDCTL::WTHRESH.val(wthresh));
+        self.modify_checked("TXDCTL", &tx.txdctl, TXDCTL::WTHRESH.val(wthresh));
+        Ok(())
+    }
+
+    /// Points transmit `queue` at `ring` and rewinds head and tail. The queue
+    /// is not enabled.
+    pub fn set_tx_ring(&mut self, queue: usize, ring: &TxRing) -> Result<(), DError> {
+        let regs = self
+            .reg()
+            .tx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        self.write_checked("TDBAL", &regs.tdbal, ring.bus_addr() as u32, u32::MAX);
+        self.write_checked(
+            "TDBAH",
+            &regs.tdbah,
+            (ring.bus_addr() >> 32) as u32,
+            u32::MAX,
+        );
+        self.write_checked("TDLEN", &regs.tdlen, ring.byte_len() as u32, u32::MAX);
+        regs.tdh.set(0);
+        regs.tdt.set(0);
+        Ok(())
+    }
+
+    /// Points receive `queue` at `ring`, selects its descriptor format in
+    /// SRRCTL and rewinds head and tail. The queue is not enabled.
+    pub fn set_rx_ring(&mut self, queue: usize, ring: &RxRing) -> Result<(), DError> {
+        let regs = self
+            .reg()
+            .rx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        self.write_checked("RDBAL", &regs.rdbal, ring.bus_addr() as u32, u32::MAX);
+        self.write_checked(
+            "RDBAH",
+            &regs.rdbah,
+            (ring.bus_addr() >> 32) as u32,
+            u32::MAX,
+        );
+        self.write_checked("RDLEN", &regs.rdlen, ring.byte_len() as u32, u32::MAX);
+        let desctype = if ring.uses_advanced_descriptors() {
+            // Advanced descriptors take the buffer size from SRRCTL instead
+            // of RCTL.BSIZE.
+            let size = ring.buffer_size();
+            if !size.is_multiple_of(1024) || !(1024..=MAX_RX_BUFFER_SIZE).contains(&size) {
+                return Err(DError::InvalidParameter);
+            }
+            let kb = size / 1024;
+            SRRCTL::DESCTYPE::AdvancedOneBuffer + SRRCTL::BSIZEPACKET.val(kb as u32)
+        } else {
+            SRRCTL::DESCTYPE::Legacy
+        };
+        self.modify_checked("SRRCTL", &regs.srrctl, desctype);
+        regs.rdh.set(0);
+        regs.rdt.set(0);
+        Ok(())
+    }
+
+    /// Enables transmit `queue` and waits until the hardware picked it up.
+    pub fn start_tx_queue(&mut self, queue: usize) -> Result<(), DError> {
+        let regs = self
+            .reg()
+            .tx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        regs.txdctl.modify(TXDCTL::ENABLE::SET);
+        wait_for(
+            "TXDCTL.ENABLE set",
+            || regs.txdctl.is_set(TXDCTL::ENABLE),
+            Duration::from_millis(1),
+            Some(10),
+        )
+    }
+
+    /// Hands transmit descriptors up to (excluding) `tail` to the hardware.
+    pub fn set_tx_tail(&mut self, queue: usize, tail: u32) {
+        mb();
+        self.reg().tx_queues[queue].tdt.set(tail);
+    }
+
+    /// Programs the TCTL collision parameters and short packet padding the
+    /// transmitter needs: CT 0x0F and COLD for full duplex, corrected on link
+    /// changes by [`Mac::set_collision_distance_for_speed`].
+    pub fn init_tctl(&mut self) {
+        self.modify_checked(
+            "TCTL",
+            &self.reg().tctl,
+            TCTL::PSP::Enabled + TCTL::CT.val(0x0F) + TCTL::COLD.val(COLLISION_DISTANCE_FD),
+        );
+    }
+
+    /// Enables receive `queue` and waits until the hardware picked it up.
+    pub fn start_rx_queue(&mut self, queue: usize) -> Result<(), DError> {
+        let regs = self
+            .reg()
+            .rx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        regs.rxdctl.modify(RXDCTL::ENABLE::SET);
+        wait_for(
+            "RXDCTL.ENABLE set",
+            || regs.rxdctl.is_set(RXDCTL::ENABLE),
+            Duration::from_millis(1),
+            Some(10),
+        )
+    }
+
+    /// Programs RCTL.BSIZE, the size of every receive buffer. Only 256, 512,
+    /// 1024 and 2048 bytes can be encoded.
+    pub fn set_rx_buffer_size(&mut self, size: usize) -> Result<(), DError> {
+        let bsize = match size {
+            2048 => RCTL::BSIZE::Bytes2048,
+            1024 => RCTL::BSIZE::Bytes1024,
+            512 => RCTL::BSIZE::Bytes512,
+            256 => RCTL::BSIZE::Bytes256,
+            _ => return Err(DError::InvalidParameter),
+        };
+        self.modify_checked("RCTL", &self.reg().rctl, bsize);
+        Ok(())
+    }
+
+    /// Reads back the descriptor base address (TDBAH:TDBAL) of `queue`.
+    pub fn tx_ring_base(&self, queue: usize) -> Result<u64, DError> {
+        let regs = self
+            .reg()
+            .tx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        Ok((regs.tdbah.get() as u64) << 32 | regs.tdbal.get() as u64)
+    }
+
+    /// Reads back the ring length in bytes (TDLEN) of `queue`.
+    pub fn tx_ring_len(&self, queue: usize) -> Result<u32, DError> {
+        let regs = self
+            .reg()
+            .tx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        Ok(regs.tdlen.get())
+    }
+
+    /// Reads back the descriptor base address (RDBAH:RDBAL) of `queue`.
+    pub fn rx_ring_base(&self, queue: usize) -> Result<u64, DError> {
+        let regs = self
+            .reg()
+            .rx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        Ok((regs.rdbah.get() as u64) << 32 | regs.rdbal.get() as u64)
+    }
+
+    /// Reads back the ring length in bytes (RDLEN) of `queue`.
+    pub fn rx_ring_len(&self, queue: usize) -> Result<u32, DError> {
+        let regs = self
+            .reg()
+            .rx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        Ok(regs.rdlen.get())
+    }
+
+    /// Caps the transmit rate of `queue` to `mbps` megabits per second, 0
+    /// removes the limit.
+    ///
+    /// The hardware paces the queue by the rate factor `link speed / mbps`,
+    /// kept with a 14 bit fraction, so the cap follows the current link speed
+    /// and must be reprogrammed after the link renegotiates. The link must be
+    /// up and `mbps` may not exceed its speed.
+    pub fn set_tx_rate_limit(&mut self, queue: usize, mbps: u32) -> Result<(), DError> {
+        if queue >= QUEUE_COUNT {
+            return Err(DError::InvalidParameter);
+        }
+        let reg = self.reg();
+
+        if mbps == 0 {
+            self.write_checked("RTTDQSEL", &reg.rttdqsel, queue as u32, u32::MAX);
+            self.write_checked("RTTBCNRC", &reg.rttbcnrc, 0, u32::MAX);
+            return Ok(());
+        }
+
+        let status = self.status();
+        let link = status.speed.mbps();
+        if !status.link_up || mbps > link {
+            return Err(DError::InvalidParameter);
+        }
+
+        let rf_int = link / mbps;
+        let rf_dec = ((link % mbps) << RTTBCNRC::RF_INT.shift) / mbps;
+
+        self.write_checked("RTTDQSEL", &reg.rttdqsel, queue as u32, u32::MAX);
+        // Rate scheduler memory window, sized for 9.5KB jumbo frames.
+        self.write_checked("RTTBCNRM", &reg.rttbcnrm, 0x14, u32::MAX);
+        self.write_checked(
+            "RTTBCNRC",
+            &reg.rttbcnrc,
+            (RTTBCNRC::RF_INT.val(rf_int) + RTTBCNRC::RF_DEC.val(rf_dec) + RTTBCNRC::RS_ENA::SET)
+                .value,
+            u32::MAX,
+        );
+        Ok(())
+    }
+
+    /// Shares the link between the transmit queues by weighted round robin:
+    /// each queue gets credits in proportion to its entry in `weights`, one
+    /// non-zero weight per queue, see [`tx_arbitration_credits`]. Unlike
+    /// [`Mac::set_tx_rate_limit`] this is not a cap, bandwidth an idle queue
+    /// leaves unused goes to the others.
+    pub fn configure_tx_arbitration(&mut self, weights: &[u8]) -> Result<(), DError> {
+        let credits = tx_arbitration_credits(weights)?;
+        let reg = self.reg();
+
+        // Hold the arbiter while the credits change under it.
+        self.modify_checked("RTTDCS", &reg.rttdcs, RTTDCS::ARBDIS::SET);
+        for (tc, &crq) in reg.rttdtcrc.iter().zip(&credits) {
+            self.write_checked(
+                "RTTDTCRC",
+                tc,
+                (RTTDTCRC::CRQ.val(crq) + RTTDTCRC::MCL.val(crq * TX_CREDIT_LIMIT_FACTOR)).value,
+                u32::MAX,
+            );
+        }
+        self.modify_checked(
+            "RTTDCS",
+            &reg.rttdcs,
+            RTTDCS::TDPAC::Weighted + RTTDCS::ARBDIS::CLEAR,
+        );
+        Ok(())
+    }
+
+    /// Reads the per-queue counters of `queue`, used to see which queue
+    /// carries the load.
+    pub fn queue_statistics(&self, queue: usize) -> Result<QueueStatistics, DError> {
+        if queue >= QUEUE_COUNT {
+            return Err(DError::InvalidParameter);
+        }
+        let stats = &self.reg().queue_stats[queue];
+        Ok(QueueStatistics {
+            tx_packets: stats.pqgptc.get(),
+        })
+    }
+
+    /// Number of packets currently held in the receive packet buffer (RDFPC).
+    /// A level that keeps growing means the host is not draining the rings
+    /// fast enough and overruns (missed packets) are about to follow.
+    pub fn rx_fifo_level(&self) -> u32 {
+        self.reg().rdfpc.get() & RDFPC_COUNT_MASK
+    }
+
+    /// Spreads received flows over the queues with RSS: programs the hash
+    /// `key` and the redirection table, then hashes TCP, UDP and IP over IPv4
+    /// and IPv6. `indirection` maps each hash bucket to a queue and must have
+    /// exactly [`DeviceKind::reta_size`] entries for `kind`.
+    pub fn configure_rss(
+        &mut self,
+        kind: DeviceKind,
+        key: &[u8; RSS_KEY_LEN],
+        indirection: &[u8],
+    ) -> Result<(), DError> {
+        let reta_size = kind.reta_size();
+        if reta_size > RETA_ENTRIES
+            || indirection.len() != reta_size
+            || indirection.iter().any(|&q| q as usize >= QUEUE_COUNT)
+        {
+            return Err(DError::InvalidParameter);
+        }
+        let reg = self.reg();
+
+        for (rssrk, bytes) in reg.rssrk.iter().zip(key.as_chunks::<4>().0) {
+            self.write_checked("RSSRK", rssrk, u32::from_le_bytes(*bytes), u32::MAX);
+        }
+        for (reta, queues) in reg.reta.iter().zip(indirection.as_chunks::<4>().0) {
+            self.write_checked("RETA", reta, u32::from_le_bytes(*queues), u32::MAX);
+        }
+        self.write_checked(
+            "MRQC",
+            &reg.mrqc,
+            (MRQC::MRQE::Rss
+                + MRQC::TCP_IPV4::SET
+                + MRQC::IPV4::SET
+                + MRQC::IPV6::SET
+                + MRQC::TCP_IPV6::SET
+                + MRQC::UDP_IPV4::SET
+                + MRQC::UDP_IPV6::SET)
+                .value,
+            u32::MAX,
+        );
+        Ok(())
+    }
+
+    /// Reports what woke the device from WUS and clears the status (WUS is
+    /// write-1-to-clear), so the next wake starts from a clean slate.
+    pub fn wake_status(&self) -> WakeStatus {
+        let wus = self.reg().wus.extract();
+        self.reg().wus.set(wus.get());
+        WakeStatus {
+            link_change: wus.is_set(WUS::LNKC),
+            magic_packet: wus.is_set(WUS::MAG),
+            directed_exact: wus.is_set(WUS::EX),
+            multicast: wus.is_set(WUS::MC),
+            broadcast: wus.is_set(WUS::BC),
+            arp: wus.is_set(WUS::ARP),
+            ipv4: wus.is_set(WUS::IPV4),
+            ipv6: wus.is_set(WUS::IPV6),
+            flex_filters: wus.read(WUS::FLX) as u8,
+        }
+    }
+
+    /// Reports which received traffic the manageability filters divert to
+    /// the BMC. Read-only, filters a host driver must leave alone when the
+    /// BMC shares the port.
+    pub fn read_management_filters(&self) -> ManagementFilters {
+        let reg = self.reg();
+        let manc = reg.manc.extract();
+        let mut decision_filters = [0; MDEF_COUNT];
+        for (filter, mdef) in decision_filters.iter_mut().zip(reg.mdef.iter()) {
+            *filter = mdef.get();
+        }
+
+        ManagementFilters {
+            tco_receive: manc.is_set(MANC::RCV_TCO_EN),
+            to_host: manc.is_set(MANC::EN_MNG2HOST),
+            to_host_filters: reg.manc2h.get() as u8,
+            decision_filters,
+        }
+    }
+
+    /// Reads the single statistics counter `stat`. Like all statistics
+    /// registers the counter is cleared by the read, other counters are left
+    /// alone.
+    pub fn read_stat(&self, stat: StatId) -> u64 {
+        let stats = &self.reg().stats;
+        let index = stat.offset() / 4;
+        // The low half must be read first, reading the high half clears both.
+        let low = stats[index].get() as u64;
+        if stat.is_64bit() {
+            low | (stats[index + 1].get() as u64) << 32
+        } else {
+            low
+        }
+    }
+
+    /// Steers packets matching `filter` to receive `queue` using the first
+    /// free 5-tuple filter, returning its index.
+    pub fn add_flow_filter(&mut self, filter: FlowFilter, queue: u8) -> Result<usize, DError> {
+        if queue as usize >= QUEUE_COUNT {
+            return Err(DError::InvalidParameter);
+        }
+        let reg = self.reg();
+        let index = (0..FLOW_FILTER_COUNT)
+            .find(|&i| !reg.ftqf[i].is_set(FTQF::QUEUE_ENABLE))
+            .ok_or(DError::NoMemory)?;
+
+        // Addresses and ports are compared in network byte order.
+        let addr = |a: Option<Ipv4Addr>| a.map_or(0, |a| u32::from_le_bytes(a.octets()));
+        let port = |p: Option<u16>| p.map_or(0, |p| u16::from_le_bytes(p.to_be_bytes()) as u32);
+        self.write_checked("SAQF", &reg.saqf[index], addr(filter.src_addr), u32::MAX);
+        self.write_checked("DAQF", &reg.daqf[index], addr(filter.dst_addr), u32::MAX);
+        self.write_checked("SPQF", &reg.spqf[index], port(filter.src_port), u32::MAX);
+
+        let mut imir = IMIR::DESTINATION_PORT.val(port(filter.dst_port));
+        if filter.dst_port.is_none() {
+            imir += IMIR::PORT_BP::SET;
+        }
+        self.write_checked("IMIR", &reg.imir[index], imir.value, u32::MAX);
+
+        // The mask bits bypass the comparison of fields left unset.
+        let mut ftqf = FTQF::PROTOCOL.val(filter.protocol.unwrap_or(0) as u32)
+            + FTQF::QUEUE.val(queue as u32)
+            + FTQF::QUEUE_ENABLE::SET;
+        if filter.protocol.is_none() {
+            ftqf += FTQF::MASK_PROTOCOL::SET;
+        }
+        if filter.src_addr.is_none() {
+            ftqf += FTQF::MASK_SOURCE_ADDR::SET;
+        }
+        if filter.dst_addr.is_none() {
+            ftqf += FTQF::MASK_DEST_ADDR::SET;
+        }
+        if filter.src_port.is_none() {
+            ftqf += FTQF::MASK_SOURCE_PORT::SET;
+        }
+        self.write_checked("FTQF", &reg.ftqf[index], ftqf.value, u32::MAX);
+
+        Ok(index)
+    }
+
+    /// Disables the flow filter at `index` returned by
+    /// [`Mac::add_flow_filter`].
+    pub fn remove_flow_filter(&mut self, index: usize) -> Result<(), DError> {
+        if index >= FLOW_FILTER_COUNT {
+            return Err(DError::InvalidParameter);
+        }
+        self.write_checked("FTQF", &self.reg().ftqf[index], 0, u32::MAX);
+        Ok(())
+    }
+
+    /// Reads the broadcast and multicast packet counters, e.g. to spot a
+    /// broadcast storm. The counters are cleared by the read.
+    pub fn cast_statistics(&self) -> CastStats {
+        CastStats {
+            rx_broadcast: self.read_stat(StatId::Bprc),
+            rx_multicast: self.read_stat(StatId::Mprc),
+            tx_broadcast: self.read_stat(StatId::Bptc),
+            tx_multicast: self.read_stat(StatId::Mptc),
+        }
+    }
+
+    /// Returns the hardware head and tail index of transmit queue 0.
+    pub fn tx_head_tail(&self) -> (u32, u32) {
+        let queue = &self.reg().tx_queues[0];
+        (queue.tdh.get(), queue.tdt.get())
+    }
+
+    /// Stops transmit queue 0, rewinds it to an empty ring and starts it
+    /// again. The ring base and length registers are kept, descriptors that
+    /// were still pending are dropped.
+    pub fn reset_tx(&mut self) -> Result<(), DError> {
+        let reg = self.reg();
+        let queue = &reg.tx_queues[0];
+
+        self.modify_checked("TCTL", &reg.tctl, TCTL::EN::Disabled);
+        queue.txdctl.modify(TXDCTL::ENABLE::CLEAR);
+        wait_for(
+            "TXDCTL.ENABLE clear",
+            || !queue.txdctl.is_set(TXDCTL::ENABLE),
+            Duration::from_millis(1),
+            Some(10),
+        )?;
+
+        queue.tdh.set(0);
+        queue.tdt.set(0);
+
+        queue.txdctl.modify(TXDCTL::ENABLE::SET);
+        wait_for(
+            "TXDCTL.ENABLE set",
+            || queue.txdctl.is_set(TXDCTL::ENABLE),
+            Duration::from_millis(1),
+            Some(10),
+        )?;
+        self.modify_checked("TCTL", &reg.tctl, TCTL::EN::Enabled);
+        Ok(())
+    }
+
+    /// Disables transmit `queue` and waits until the hardware stopped
+    /// fetching from its ring.
+    pub fn stop_tx_queue(&mut self, queue: usize) -> Result<(), DError> {
+        let regs = self
+            .reg()
+            .tx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        regs.txdctl.modify(TXDCTL::ENABLE::CLEAR);
+        wait_for(
+            "TXDCTL.ENABLE clear",
+            || !regs.txdctl.is_set(TXDCTL::ENABLE),
             Duration::from_millis(1),
-            Some(1000),
+            Some(10),
         )
     }
 
+    /// Disables receive `queue` and waits until the hardware stopped writing
+    /// to its ring.
+    pub fn stop_rx_queue(&mut self, queue: usize) -> Result<(), DError> {
+        let regs = self
+            .reg()
+            .rx_queues
+            .get(queue)
+            .ok_or(DError::InvalidParameter)?;
+        regs.rxdctl.modify(RXDCTL::ENABLE::CLEAR);
+        wait_for(
+            "RXDCTL.ENABLE clear",
+            || !regs.rxdctl.is_set(RXDCTL::ENABLE),
+            Duration::from_millis(1),
+            Some(10),
+        )
+    }
+
+    /// Sets CTRL.SLU. Full duplex is only forced for direct copper links,
+    /// on SGMII and SerDes the duplex is resolved by negotiation.
     pub fn set_link_up(&mut self) {
-        self.reg_mut().ctrl.modify(CTRL::SLU::SET + CTRL::FD::SET);
+        let mut ctrl = CTRL::SLU::SET;
+        if let Some(LinkMode::DirectCooper) = self.link_mode() {
+            ctrl += CTRL::FD::SET;
+        }
+        self.modify_checked("CTRL", &self.reg().ctrl, ctrl);
     }
 }
 
@@ -136,6 +1344,8 @@ register_structs! {
         (0x4 => _rsv1),
         (0x8 => status: ReadOnly<u32, STATUS::Register>),
         (0xC => _rsv2),
+        (0x10 => eec: ReadWrite<u32, EEC::Register>),
+        (0x14 => eerd: ReadWrite<u32, EERD::Register>),
         (0x18 => ctrl_ext: ReadWrite<u32, CTRL_EXT::Register>),
         (0x1c => _rsv3),
         (0x20 => mdic: ReadWrite<u32, MDIC::Register>),
@@ -150,8 +1360,13 @@ register_structs! {
         (0x104 => _rsv7),
         (0x400 => tctl: ReadWrite<u32, TCTL::Register>),
         (0x404 => _rsv12),
+        (0x410 => tipg: ReadWrite<u32, TIPG::Register>),
+        (0x414 => _rsv28),
+        (0xE00 => ledctl: ReadWrite<u32, LEDCTL::Register>),
+        (0xE04 => _rsv24),
         (0x1514 => gpie: ReadWrite<u32, GPIE::Register>),
         (0x1518 => _rsv16),
+        (0x1520 => eics: ReadWrite<u32>),
         (0x1524 => eims: ReadWrite<u32>),
         (0x1528 => eimc: ReadWrite<u32>),
         (0x152c => eiac: ReadWrite<u32>),
@@ -159,18 +1374,368 @@ register_structs! {
         (0x1534 => _rsv5),
         (0x1580 => eicr: ReadWrite<u32>),
         (0x1584 => _rsv6),
+        (0x1680 => eitr: [ReadWrite<u32, EITR::Register>; VECTOR_COUNT]),
+        (0x1694 => _rsv18),
+        (0x2430 => rdfpc: ReadOnly<u32>),
+        (0x2434 => _rsv29),
+        (0x3600 => rttdcs: ReadWrite<u32, RTTDCS::Register>),
+        (0x3604 => rttdqsel: ReadWrite<u32>),
+        (0x3608 => _rsv20),
+        (0x3610 => rttdtcrc: [ReadWrite<u32, RTTDTCRC::Register>; QUEUE_COUNT]),
+        (0x3620 => _rsv36),
+        (0x3690 => rttbcnrm: ReadWrite<u32>),
+        (0x3694 => _rsv21),
+        (0x36B0 => rttbcnrc: ReadWrite<u32, RTTBCNRC::Register>),
+        (0x36B4 => _rsv22),
+        (0x4000 => stats: [ReadOnly<u32>; STAT_REG_COUNT]),
+        (0x4100 => _rsv25),
+        (0x5200 => mta: [ReadWrite<u32>; MTA_COUNT]),
         (0x5400 => ralh_0_15: [ReadWrite<u32>; 32]),
         (0x5480 => _rsv8),
         (0x54e0 => ralh_16_23: [ReadWrite<u32>;32]),
         (0x5560 => _rsv9),
+        (0x5810 => wus: ReadWrite<u32, WUS::Register>),
+        (0x5814 => _rsv33),
+        (0x5818 => mrqc: ReadWrite<u32, MRQC::Register>),
+        (0x581C => _rsv34),
+        (0x5820 => manc: ReadOnly<u32, MANC::Register>),
+        (0x5824 => _rsv30),
+        (0x5860 => manc2h: ReadOnly<u32>),
+        (0x5864 => _rsv31),
+        (0x5890 => mdef: [ReadOnly<u32>; MDEF_COUNT]),
+        (0x58B0 => _rsv32),
+        (0x5980 => saqf: [ReadWrite<u32>; FLOW_FILTER_COUNT]),
+        (0x59A0 => daqf: [ReadWrite<u32>; FLOW_FILTER_COUNT]),
+        (0x59C0 => spqf: [ReadWrite<u32>; FLOW_FILTER_COUNT]),
+        (0x59E0 => ftqf: [ReadWrite<u32, FTQF::Register>; FLOW_FILTER_COUNT]),
+        (0x5A00 => _rsv26),
+        (0x5A80 => imir: [ReadWrite<u32, IMIR::Register>; FLOW_FILTER_COUNT]),
+        (0x5AA0 => _rsv27),
         (0x5B50 => swsm: ReadWrite<u32, SWSM::Register>),
-        (0x5B54 => fwsm: ReadWrite<u32>),
+        (0x5B54 => fwsm: ReadWrite<u32, FWSM::Register>),
         (0x5B58 => _rsv10),
-        (0x5B5C => sw_fw_sync: ReadWrite<u32>),
+        (0x5B5C => sw_fw_sync: ReadWrite<u32, SW_FW_SYNC::Register>),
         (0x5B60 => _rsv11),
+        (0x5C00 => reta: [ReadWrite<u32>; RETA_ENTRIES / 4]),
+        (0x5C80 => rssrk: [ReadWrite<u32>; RSS_KEY_LEN / 4]),
+        (0x5CA8 => _rsv35),
+        (0xC000 => rx_queues: [RxQueueRegisters; QUEUE_COUNT]),
+        (0xC100 => _rsv19),
+        (0xE000 => tx_queues: [TxQueueRegisters; QUEUE_COUNT]),
+        (0xE100 => _rsv17),
+        (0x10000 => queue_stats: [QueueStatRegisters; QUEUE_COUNT]),
 
         // The end of the struct is marked as follows.
-        (0xEFFF => @END),
+        (0x10400 => @END),
+    }
+}
+
+/// Redirection table entries mapped, see [`crate::DeviceKind::reta_size`].
+pub const RETA_ENTRIES: usize = 128;
+/// Length of the RSS hash key in bytes.
+pub const RSS_KEY_LEN: usize = 40;
+
+/// Number of RX/TX queue register blocks mapped, the i210 has 4.
+pub const QUEUE_COUNT: usize = 4;
+/// An MDIC transaction takes tens of microseconds, give up after ~100ms.
+const MDIC_POLL_INTERVAL: Duration = Duration::from_micros(50);
+const MDIC_POLL_COUNT: usize = 2000;
+
+/// Polling of the SW/FW semaphores, giving firmware about a second to let
+/// go of a resource.
+const SWFW_SYNC_INTERVAL: Duration = Duration::from_millis(5);
+const SWFW_SYNC_TRIES: usize = 200;
+const HW_SEMAPHORE_TRIES: usize = 2000;
+/// Outstanding DMA requests drain within ~80ms after GIO_MASTER_DISABLE.
+const GIO_MASTER_TRIES: usize = 800;
+/// An EERD read completes in microseconds unless the NVM is busy with an
+/// auto-read, give up after ~10ms.
+const EERD_POLL_INTERVAL: Duration = Duration::from_micros(5);
+const EERD_POLL_COUNT: usize = 2000;
+
+/// Transmit arbitration credits per unit of weight, in 64 byte units: one
+/// standard 1536 byte frame.
+const TX_CREDIT_PER_WEIGHT: u32 = 1536 / 64;
+/// A queue may bank this many refills of credit while it waits.
+const TX_CREDIT_LIMIT_FACTOR: u32 = 2;
+
+/// Credit refill quantum of each transmit queue for
+/// [`Mac::configure_tx_arbitration`]: `weights` has one non-zero entry per
+/// queue and each unit of weight is worth one full-sized frame per round.
+pub fn tx_arbitration_credits(weights: &[u8]) -> Result<[u32; QUEUE_COUNT], DError> {
+    if weights.len() != QUEUE_COUNT || weights.contains(&0) {
+        return Err(DError::InvalidParameter);
+    }
+    let mut credits = [0; QUEUE_COUNT];
+    for (crq, &weight) in credits.iter_mut().zip(weights) {
+        *crq = weight as u32 * TX_CREDIT_PER_WEIGHT;
+    }
+    Ok(credits)
+}
+
+/// TCTL.COLD for full duplex links.
+const COLLISION_DISTANCE_FD: u32 = 0x40;
+/// TCTL.COLD for half duplex 10/100 links.
+const COLLISION_DISTANCE_HD: u32 = 0x200;
+
+/// Descriptor prefetch (PTHRESH) and host (HTHRESH) thresholds applied by
+/// [`Mac::load_defaults`].
+const RX_PTHRESH: u32 = 8;
+const RX_HTHRESH: u32 = 8;
+const TX_PTHRESH: u32 = 8;
+const TX_HTHRESH: u32 = 1;
+
+/// Number of receive address filters (RAL/RAH pairs).
+pub const RAR_COUNT: usize = 24;
+/// Address valid bit in RAH.
+const RAH_AV: u32 = 1 << 31;
+/// Address bytes 4 and 5 in RAH, the rest are the AV and select bits.
+const RAH_ADDR_MASK: u32 = 0xffff;
+/// i210 receive queue a matching frame is steered to, honored with
+/// QSEL_ENABLE. The 82576 uses these bits as POOLSEL instead.
+const RAH_QSEL_SHIFT: u32 = 18;
+const RAH_QSEL_MASK: u32 = 0b11 << RAH_QSEL_SHIFT;
+const RAH_QSEL_ENABLE: u32 = 1 << 28;
+
+/// Number of 32-bit Multicast Table Array registers (4096 hash bits).
+pub const MTA_COUNT: usize = 128;
+
+/// Hashes a multicast address into a bit index of the MTA. `mo` is the
+/// RCTL.MO value choosing which 12 bits of the last two bytes are used.
+fn multicast_hash(addr: &MacAddr6, mo: u32) -> usize {
+    let b = addr.bytes();
+    let (lo, hi) = (b[4] as usize, b[5] as usize);
+    let hash = match mo {
+        0 => (lo >> 4) | (hi << 4),
+        1 => (lo >> 3) | (hi << 5),
+        2 => (lo >> 2) | (hi << 6),
+        _ => lo | (hi << 8),
+    };
+    hash & 0xfff
+}
+
+/// Number of 5-tuple flow filters.
+pub const FLOW_FILTER_COUNT: usize = 8;
+
+/// Fields a flow filter matches on, `None` fields are not compared. See
+/// [`Mac::add_flow_filter`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct FlowFilter {
+    /// IP protocol number, e.g. 6 for TCP.
+    pub protocol: Option<u8>,
+    pub src_addr: Option<Ipv4Addr>,
+    pub dst_addr: Option<Ipv4Addr>,
+    pub src_port: Option<u16>,
+    pub dst_port: Option<u16>,
+}
+
+/// Packet count field of RDFPC.
+const RDFPC_COUNT_MASK: u32 = 0x1fff;
+
+/// Number of manageability decision filters (MDEF).
+pub const MDEF_COUNT: usize = 8;
+
+/// Wake-up causes, see [`Mac::wake_status`]. All false after a wake that
+/// did not come from the network.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WakeStatus {
+    pub link_change: bool,
+    pub magic_packet: bool,
+    /// A frame matching one of the receive address filters.
+    pub directed_exact: bool,
+    pub multicast: bool,
+    pub broadcast: bool,
+    pub arp: bool,
+    pub ipv4: bool,
+    pub ipv6: bool,
+    /// Bit n set: flexible filter n matched.
+    pub flex_filters: u8,
+}
+
+/// Manageability filter state, see [`Mac::read_management_filters`].
+#[derive(Debug, Clone, Copy)]
+pub struct ManagementFilters {
+    /// Receive TCO packets are routed to the BMC (MANC.RCV_TCO_EN).
+    pub tco_receive: bool,
+    /// Packets matched by the filters in `to_host_filters` are also passed
+    /// to the host (MANC.EN_MNG2HOST).
+    pub to_host: bool,
+    /// Bit n set: packets matching decision filter n also go to the host
+    /// (MANC2H).
+    pub to_host_filters: u8,
+    /// Raw decision filter n (MDEF), 0 when the filter is unused.
+    pub decision_filters: [u32; MDEF_COUNT],
+}
+
+impl ManagementFilters {
+    /// Whether any traffic is diverted to the BMC at all.
+    pub fn diverts_traffic(&self) -> bool {
+        self.tco_receive && self.decision_filters.iter().any(|&f| f != 0)
+    }
+}
+
+/// Number of 32-bit statistics registers mapped from 0x4000.
+const STAT_REG_COUNT: usize = 64;
+
+/// Statistics counters readable with [`Mac::read_stat`]. The 64-bit octet
+/// counters span a low/high register pair.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StatId {
+    /// CRC error count.
+    Crcerrs,
+    /// Alignment error count.
+    Algnerrc,
+    /// Symbol error count.
+    Symerrs,
+    /// RX error count.
+    Rxerrc,
+    /// Missed packets count.
+    Mpc,
+    /// Single collision count.
+    Scc,
+    /// Excessive collisions count.
+    Ecol,
+    /// Multiple collision count.
+    Mcc,
+    /// Late collisions count.
+    Latecol,
+    /// Collision count.
+    Colc,
+    /// Receive length error count.
+    Rlec,
+    /// Good packets received count.
+    Gprc,
+    /// Broadcast packets received count.
+    Bprc,
+    /// Multicast packets received count.
+    Mprc,
+    /// Good packets transmitted count.
+    Gptc,
+    /// Good octets received count, 64-bit.
+    Gorc,
+    /// Good octets transmitted count, 64-bit.
+    Gotc,
+    /// Receive no buffers count.
+    Rnbc,
+    /// Receive undersize count.
+    Ruc,
+    /// Receive oversize count.
+    Roc,
+    /// Total octets received, 64-bit.
+    Tor,
+    /// Total octets transmitted, 64-bit.
+    Tot,
+    /// Total packets received.
+    Tpr,
+    /// Total packets transmitted.
+    Tpt,
+    /// Multicast packets transmitted count.
+    Mptc,
+    /// Broadcast packets transmitted count.
+    Bptc,
+}
+
+impl StatId {
+    /// Offset of the (low) register from the start of the statistics block.
+    fn offset(&self) -> usize {
+        match self {
+            StatId::Crcerrs => 0x00,
+            StatId::Algnerrc => 0x04,
+            StatId::Symerrs => 0x08,
+            StatId::Rxerrc => 0x0C,
+            StatId::Mpc => 0x10,
+            StatId::Scc => 0x14,
+            StatId::Ecol => 0x18,
+            StatId::Mcc => 0x1C,
+            StatId::Latecol => 0x20,
+            StatId::Colc => 0x28,
+            StatId::Rlec => 0x40,
+            StatId::Gprc => 0x74,
+            StatId::Bprc => 0x78,
+            StatId::Mprc => 0x7C,
+            StatId::Gptc => 0x80,
+            StatId::Gorc => 0x88,
+            StatId::Gotc => 0x90,
+            StatId::Rnbc => 0xA0,
+            StatId::Ruc => 0xA4,
+            StatId::Roc => 0xAC,
+            StatId::Tor => 0xC0,
+            StatId::Tot => 0xC8,
+            StatId::Tpr => 0xD0,
+            StatId::Tpt => 0xD4,
+            StatId::Mptc => 0xF0,
+            StatId::Bptc => 0xF4,
+        }
+    }
+
+    fn is_64bit(&self) -> bool {
+        matches!(
+            self,
+            StatId::Gorc | StatId::Gotc | StatId::Tor | StatId::Tot
+        )
+    }
+}
+
+/// Broadcast and multicast packet counts, see [`Mac::cast_statistics`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct CastStats {
+    pub rx_broadcast: u64,
+    pub rx_multicast: u64,
+    pub tx_broadcast: u64,
+    pub tx_multicast: u64,
+}
+
+/// Per-queue counters, see [`Mac::queue_statistics`].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct QueueStatistics {
+    /// Good packets transmitted on the queue (PQGPTC).
+    pub tx_packets: u32,
+}
+
+/// Number of MSI-X vectors (EITR registers), the i210 has 5.
+pub const VECTOR_COUNT: usize = 5;
+
+// 每个接收队列的寄存器组, 队列 n 位于 0xC000 + 0x40 * n
+register_structs! {
+    pub RxQueueRegisters {
+        (0x00 => rdbal: ReadWrite<u32>),
+        (0x04 => rdbah: ReadWrite<u32>),
+        (0x08 => rdlen: ReadWrite<u32>),
+        (0x0C => srrctl: ReadWrite<u32, SRRCTL::Register>),
+        (0x10 => rdh: ReadWrite<u32>),
+        (0x14 => _rsv0),
+        (0x18 => rdt: ReadWrite<u32>),
+        (0x1C => _rsv1),
+        (0x28 => rxdctl: ReadWrite<u32, RXDCTL::Register>),
+        (0x2C => _rsv2),
+        (0x40 => @END),
+    }
+}
+
+// 每个队列的统计寄存器组, 队列 n 位于 0x10000 + 0x100 * n
+register_structs! {
+    pub QueueStatRegisters {
+        (0x00 => _rsv0),
+        (0x14 => pqgptc: ReadOnly<u32>),
+        (0x18 => _rsv1),
+        (0x100 => @END),
+    }
+}
+
+// 每个发送队列的寄存器组, 队列 n 位于 0xE000 + 0x40 * n
+register_structs! {
+    pub TxQueueRegisters {
+        (0x00 => tdbal: ReadWrite<u32>),
+        (0x04 => tdbah: ReadWrite<u32>),
+        (0x08 => tdlen: ReadWrite<u32>),
+        (0x0C => _rsv0),
+        (0x10 => tdh: ReadWrite<u32>),
+        (0x14 => _rsv1),
+        (0x18 => tdt: ReadWrite<u32>),
+        (0x1C => _rsv2),
+        (0x28 => txdctl: ReadWrite<u32, TXDCTL::Register>),
+        (0x2C => _rsv3),
+        (0x40 => @END),
     }
 }
 
@@ -183,6 +1748,7 @@ register_bitfields! [
             HalfDuplex = 0,
             FullDuplex = 1,
         ],
+        GIO_MASTER_DISABLE OFFSET(2) NUMBITS(1)[],
         SLU OFFSET(6) NUMBITS(1)[],
         SPEED OFFSET(8) NUMBITS(2)[
             Speed10 = 0,
@@ -209,6 +1775,22 @@ register_bitfields! [
             Speed1000 = 0b10,
         ],
          PHYRA OFFSET(10) NUMBITS(1)[],
+        GIO_MASTER_ENABLE OFFSET(19) NUMBITS(1)[],
+    ],
+    // EEPROM/Flash Control - EEC (0x10)
+    EEC [
+        EE_PRES OFFSET(8) NUMBITS(1)[],         // NVM present
+        AUTO_RD OFFSET(9) NUMBITS(1)[],         // NVM auto-read done
+        EE_SIZE OFFSET(11) NUMBITS(4)[],        // NVM size, 2^(EE_SIZE + 6) words
+        FLASH_DETECTED OFFSET(19) NUMBITS(1)[], // i210: external flash detected
+        FLUPD OFFSET(23) NUMBITS(1)[],          // i210: flash update
+    ],
+    // EEPROM Read - EERD (0x14)
+    EERD [
+        START OFFSET(0) NUMBITS(1)[],
+        DONE OFFSET(1) NUMBITS(1)[],
+        ADDR OFFSET(2) NUMBITS(14)[],
+        DATA OFFSET(16) NUMBITS(16)[],
     ],
     pub CTRL_EXT [
         LINK_MODE OFFSET(22) NUMBITS(2)[
@@ -244,6 +1826,39 @@ register_bitfields! [
         EEUR OFFSET(3) NUMBITS(1)[],
     ],
 
+    MRQC [
+        MRQE OFFSET(0) NUMBITS(3)[
+            Disabled = 0b000,
+            Rss = 0b010,
+        ],
+        TCP_IPV4 OFFSET(16) NUMBITS(1)[],
+        IPV4 OFFSET(17) NUMBITS(1)[],
+        IPV6 OFFSET(20) NUMBITS(1)[],
+        TCP_IPV6 OFFSET(21) NUMBITS(1)[],
+        UDP_IPV4 OFFSET(22) NUMBITS(1)[],
+        UDP_IPV6 OFFSET(23) NUMBITS(1)[],
+    ],
+
+    WUS [
+        LNKC OFFSET(0) NUMBITS(1)[],
+        MAG OFFSET(1) NUMBITS(1)[],
+        EX OFFSET(2) NUMBITS(1)[],
+        MC OFFSET(3) NUMBITS(1)[],
+        BC OFFSET(4) NUMBITS(1)[],
+        ARP OFFSET(5) NUMBITS(1)[],
+        IPV4 OFFSET(6) NUMBITS(1)[],
+        IPV6 OFFSET(7) NUMBITS(1)[],
+        FLX OFFSET(16) NUMBITS(8)[],
+    ],
+
+    FWSM [
+        EEP_FW_SEMAPHORE OFFSET(0) NUMBITS(1)[],
+        FW_MODE OFFSET(1) NUMBITS(3)[
+            None = 0,
+        ],
+        FW_VAL OFFSET(15) NUMBITS(1)[],
+    ],
+
     SW_FW_SYNC [
         SW_EEP_SM OFFSET(0) NUMBITS(1)[],
         SW_PHY_SM0 OFFSET(1) NUMBITS(1)[],
@@ -327,6 +1942,17 @@ register_bitfields! [
         ],
     ],
 
+    // LED Control - LEDCTL (0xE00), fields of LED0. LED1/LED2 repeat at +8/+16.
+    LEDCTL [
+        LED0_MODE OFFSET(0) NUMBITS(4)[
+            LinkUp = 0x2,
+            On = 0xE,
+            Off = 0xF,
+        ],
+        LED0_IVRT OFFSET(6) NUMBITS(1)[],
+        LED0_BLINK OFFSET(7) NUMBITS(1)[],
+    ],
+
     // Transmit Control Register - TCTL (0x400)
     TCTL [
         EN OFFSET(1) NUMBITS(1)[
@@ -345,6 +1971,68 @@ register_bitfields! [
         MULR OFFSET(28) NUMBITS(1)[],
     ],
 
+    // Transmit IPG - TIPG (0x0410)
+    TIPG [
+        IPGT OFFSET(0) NUMBITS(10)[],
+        IPGR1 OFFSET(10) NUMBITS(10)[],
+        IPGR2 OFFSET(20) NUMBITS(10)[],
+    ],
+
+    // Split and Replication Receive Control - SRRCTL (0xC00C + 0x40 * n)
+    SRRCTL [
+        BSIZEPACKET OFFSET(0) NUMBITS(7)[], // packet buffer size in 1 KB units
+        DESCTYPE OFFSET(25) NUMBITS(3)[
+            Legacy = 0b000,
+            AdvancedOneBuffer = 0b001,
+        ],
+    ],
+
+    // Receive Descriptor Control - RXDCTL (0xC028 + 0x40 * n)
+    RXDCTL [
+        PTHRESH OFFSET(0) NUMBITS(5)[],
+        HTHRESH OFFSET(8) NUMBITS(5)[],
+        WTHRESH OFFSET(16) NUMBITS(5)[],
+        ENABLE OFFSET(25) NUMBITS(1)[],
+        SWFLSH OFFSET(26) NUMBITS(1)[],
+    ],
+
+    // Transmit Descriptor Control - TXDCTL (0xE028 + 0x40 * n)
+    TXDCTL [
+        PTHRESH OFFSET(0) NUMBITS(5)[],
+        HTHRESH OFFSET(8) NUMBITS(5)[],
+        WTHRESH OFFSET(16) NUMBITS(5)[],
+        ENABLE OFFSET(25) NUMBITS(1)[],
+        SWFLSH OFFSET(26) NUMBITS(1)[],
+    ],
+
+    // Tx Descriptor Plane Control and Status - RTTDCS (0x3600)
+    RTTDCS [
+        TDPAC OFFSET(0) NUMBITS(1)[ // Descriptor plane arbitration
+            RoundRobin = 0,
+            Weighted = 1,
+        ],
+        ARBDIS OFFSET(6) NUMBITS(1)[], // Arbiter disable
+    ],
+
+    // Tx Descriptor Plane TC Rate-Scheduler Config - RTTDTCRC (0x3610 + 4 * n)
+    RTTDTCRC [
+        CRQ OFFSET(0) NUMBITS(14)[],  // Credit refill quantum in 64 byte units
+        MCL OFFSET(14) NUMBITS(16)[], // Max credit limit in 64 byte units
+    ],
+
+    // Tx BCN Rate-Scheduler Config - RTTBCNRC (0x36B0), for the queue in RTTDQSEL
+    RTTBCNRC [
+        RF_DEC OFFSET(0) NUMBITS(14)[],  // Rate factor, fractional part in 1/2^14
+        RF_INT OFFSET(14) NUMBITS(10)[], // Rate factor, integer part
+        RS_ENA OFFSET(31) NUMBITS(1)[],  // Rate scheduler enable
+    ],
+
+    // Extended Interrupt Throttle - EITR (0x1680 + 4 * n)
+    EITR [
+        INTERVAL OFFSET(2) NUMBITS(13)[], // Minimum inter-interrupt interval in 1us
+        CNT_IGNR OFFSET(31) NUMBITS(1)[], // Do not update the internal counter on write
+    ],
+
     // Extended Interrupt Cause Register - EICR (0x01580)
     EICR [
         // Non MSI-X mode (GPIE.Multiple_MSIX = 0)
@@ -378,7 +2066,7 @@ register_bitfields! [
     ],
 
     // Legacy Interrupt Cause Register - ICR (0x000C0)
-    ICR [
+    pub ICR [
         TXDW OFFSET(0) NUMBITS(1)[],   // Transmit Descriptor Written Back
         TXQE OFFSET(1) NUMBITS(1)[],   // Transmit Queue Empty
         LSC OFFSET(2) NUMBITS(1)[],    // Link Status Change
@@ -444,6 +2132,29 @@ register_bitfields! [
         DOCK OFFSET(19) NUMBITS(1)[],  // Dock/Undock Status Change
     ],
 
+    // Management Control - MANC (0x5820)
+    MANC [
+        RCV_TCO_EN OFFSET(17) NUMBITS(1)[],
+        EN_MNG2HOST OFFSET(21) NUMBITS(1)[],
+    ],
+
+    // Five-Tuple Queue Filter - FTQF (0x59E0 + 4 * n)
+    FTQF [
+        PROTOCOL OFFSET(0) NUMBITS(8)[],
+        QUEUE_ENABLE OFFSET(8) NUMBITS(1)[],
+        QUEUE OFFSET(16) NUMBITS(10)[],
+        MASK_PROTOCOL OFFSET(28) NUMBITS(1)[],
+        MASK_SOURCE_ADDR OFFSET(29) NUMBITS(1)[],
+        MASK_DEST_ADDR OFFSET(30) NUMBITS(1)[],
+        MASK_SOURCE_PORT OFFSET(31) NUMBITS(1)[],
+    ],
+
+    // Immediate Interrupt Rx - IMIR (0x5A80 + 4 * n)
+    IMIR [
+        DESTINATION_PORT OFFSET(0) NUMBITS(16)[],
+        PORT_BP OFFSET(17) NUMBITS(1)[],
+    ],
+
     // General Purpose Interrupt Enable - GPIE (0x1514)
     GPIE [
         NSICR OFFSET(0) NUMBITS(1)[
@@ -466,6 +2177,212 @@ register_bitfields! [
     ],
 ];
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NvmType {
+    /// SPI EEPROM, e.g. on 82576 boards.
+    Eeprom,
+    /// External flash (i210).
+    Flash,
+    /// Integrated OTP memory of flashless i210/i211 parts, also reported when
+    /// no NVM is present at all.
+    Invm,
+}
+
+/// NVM description decoded from EEC, see [`Mac::nvm_info`].
+#[derive(Debug, Clone, Copy)]
+pub struct NvmInfo {
+    pub kind: NvmType,
+    /// Size in 16-bit words, meaningless for [`NvmType::Invm`].
+    pub size_words: usize,
+    /// The hardware finished loading its configuration from the NVM.
+    pub auto_read_done: bool,
+}
+
+/// Interrupt behavior decoded from GPIE, see [`Mac::gpie_config`].
+#[derive(Debug, Clone, Copy)]
+pub struct GpieConfig {
+    /// EICR is cleared on read instead of only by writing 1s.
+    pub clear_on_read: bool,
+    /// MSI-X with one vector per cause instead of a single vector.
+    pub multiple_msix: bool,
+    /// Low latency credits increment rate.
+    pub ll_interval: u8,
+    /// EIMS bits selected by EIAM are cleared when their interrupt asserts.
+    pub auto_mask: bool,
+    /// PBA reports MSI-X pending bits rather than legacy EICR bits.
+    pub pba_msix: bool,
+}
+
+impl GpieConfig {
+    pub fn decode(gpie: u32) -> Self {
+        let gpie = LocalRegisterCopy::<u32, GPIE::Register>::new(gpie);
+        Self {
+            clear_on_read: gpie.is_set(GPIE::NSICR),
+            multiple_msix: gpie.is_set(GPIE::Multiple_MSIX),
+            ll_interval: gpie.read(GPIE::LL_Interval) as u8,
+            auto_mask: gpie.is_set(GPIE::EIAME),
+            pba_msix: gpie.is_set(GPIE::PBA_Support),
+        }
+    }
+}
+
+/// Extended interrupt causes (EICR bits), see [`Mac::trigger_interrupt`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InterruptCause {
+    /// The cause mapped to MSI-X vector `n`.
+    Vector(u8),
+    /// TCP timer expiry.
+    TcpTimer,
+    /// Any of the causes reported through ICR, e.g. link status change.
+    Other,
+}
+
+/// EICR TCP timer and other cause bits.
+const EICR_TCP_TIMER: u32 = 1 << 30;
+const EICR_OTHER: u32 = 1 << 31;
+
+/// Restores the interrupt masks saved by [`Mac::mask_all_scoped`] on drop.
+pub struct InterruptGuard<'a> {
+    mac: &'a mut Mac,
+    ims: u32,
+    eims: u32,
+}
+
+impl InterruptGuard<'_> {
+    /// The masked device, for reconfiguring it while the guard is held.
+    pub fn mac(&mut self) -> &mut Mac {
+        self.mac
+    }
+}
+
+impl Drop for InterruptGuard<'_> {
+    fn drop(&mut self) {
+        let reg = self.mac.reg();
+        reg.ims.set(self.ims);
+        reg.eims.set(self.eims);
+    }
+}
+
+/// Snapshot of the legacy interrupt cause register.
+pub type InterruptCauses = LocalRegisterCopy<u32, ICR::Register>;
+
+bitflags::bitflags! {
+    /// Legacy interrupt causes (ICR/IMS bits), see
+    /// [`Mac::enable_interrupt_causes`].
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct InterruptMask: u32 {
+        /// Transmit descriptor written back.
+        const TX_DONE = 1 << 0;
+        const TX_QUEUE_EMPTY = 1 << 1;
+        const LINK_STATUS_CHANGE = 1 << 2;
+        const RX_SEQUENCE_ERROR = 1 << 3;
+        /// Free receive descriptors fell below the minimum threshold.
+        const RX_DESC_MINIMUM = 1 << 4;
+        const RX_OVERRUN = 1 << 6;
+        const RX_TIMER = 1 << 7;
+        const MDIO_ACCESS_COMPLETE = 1 << 9;
+        const MANAGEMENT = 1 << 18;
+        const DOCK = 1 << 19;
+    }
+}
+
+/// Decoded legacy interrupt causes, see [`Mac::read_interrupt_cause`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct InterruptSummary {
+    pub tx_descriptor_written: bool,
+    pub tx_queue_empty: bool,
+    pub link_status_change: bool,
+    pub rx_sequence_error: bool,
+    /// Free receive descriptors fell below the minimum threshold.
+    pub rx_descriptor_minimum: bool,
+    pub rx_overrun: bool,
+    pub rx_timer: bool,
+    pub mdio_access_complete: bool,
+    /// The device asserted its interrupt, false for a shared line raised by
+    /// another device.
+    pub asserted: bool,
+}
+
+impl InterruptSummary {
+    /// Yields each set cause, for dispatching to per-cause handlers.
+    /// `asserted` is not a cause and never yielded.
+    pub fn iter(&self) -> impl Iterator<Item = Cause> {
+        [
+            (self.tx_descriptor_written, Cause::TxDescriptorWritten),
+            (self.tx_queue_empty, Cause::TxQueueEmpty),
+            (self.link_status_change, Cause::LinkStatusChange),
+            (self.rx_sequence_error, Cause::RxSequenceError),
+            (self.rx_descriptor_minimum, Cause::RxDescriptorMinimum),
+            (self.rx_overrun, Cause::RxOverrun),
+            (self.rx_timer, Cause::RxTimer),
+            (self.mdio_access_complete, Cause::MdioAccessComplete),
+        ]
+        .into_iter()
+        .filter_map(|(set, cause)| set.then_some(cause))
+    }
+}
+
+/// One legacy interrupt cause, see [`InterruptSummary::iter`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Cause {
+    TxDescriptorWritten,
+    TxQueueEmpty,
+    LinkStatusChange,
+    RxSequenceError,
+    RxDescriptorMinimum,
+    RxOverrun,
+    RxTimer,
+    MdioAccessComplete,
+}
+
+impl From<InterruptCauses> for InterruptSummary {
+    fn from(icr: InterruptCauses) -> Self {
+        Self {
+            tx_descriptor_written: icr.is_set(ICR::TXDW),
+            tx_queue_empty: icr.is_set(ICR::TXQE),
+            link_status_change: icr.is_set(ICR::LSC),
+            rx_sequence_error: icr.is_set(ICR::RXSEQ),
+            rx_descriptor_minimum: icr.is_set(ICR::RXDMT0),
+            rx_overrun: icr.is_set(ICR::RXO),
+            rx_timer: icr.is_set(ICR::RXT0),
+            mdio_access_complete: icr.is_set(ICR::MDAC),
+            asserted: icr.is_set(ICR::INT_ASSERTED),
+        }
+    }
+}
+
+/// Interrupt moderation presets for [`crate::Igb::set_interrupt_moderation`].
+///
+/// | preset     | EITR interval       | RXDCTL/TXDCTL WTHRESH |
+/// |------------|---------------------|-----------------------|
+/// | LowLatency | 0 (every event)     | 1                     |
+/// | Balanced   | 50us (~20k irq/s)   | 4                     |
+/// | Throughput | 250us (~4k irq/s)   | 16                    |
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModerationPreset {
+    LowLatency,
+    Balanced,
+    Throughput,
+}
+
+impl ModerationPreset {
+    pub fn interval_us(&self) -> u32 {
+        match self {
+            ModerationPreset::LowLatency => 0,
+            ModerationPreset::Balanced => 50,
+            ModerationPreset::Throughput => 250,
+        }
+    }
+
+    pub fn write_back_threshold(&self) -> u32 {
+        match self {
+            ModerationPreset::LowLatency => 1,
+            ModerationPreset::Balanced => 4,
+            ModerationPreset::Throughput => 16,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum LinkMode {
     DirectCooper,
@@ -488,6 +2405,16 @@ pub enum Speed {
     Mb10,
 }
 
+impl Speed {
+    pub fn mbps(&self) -> u32 {
+        match self {
+            Speed::Mb1000 => 1000,
+            Speed::Mb100 => 100,
+            Speed::Mb10 => 10,
+        }
+    }
+}
+
 #[derive(Clone, Copy, PartialEq, Eq)]
 #[repr(transparent)]
 pub struct MacAddr6([u8; 6]);
@@ -512,6 +2439,34 @@ impl Debug for MacAddr6 {
     }
 }
 
+/// Parses `xx:xx:xx:xx:xx:xx`, or the same with `-` separators. Mixing
+/// separators is rejected.
+impl FromStr for MacAddr6 {
+    type Err = DError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let sep = match s.as_bytes().get(2) {
+            Some(b':') => ':',
+            Some(b'-') => '-',
+            _ => return Err(DError::InvalidParameter),
+        };
+        let mut bytes = [0u8; 6];
+        let mut parts = s.split(sep);
+        for byte in bytes.iter_mut() {
+            let part = parts.next().ok_or(DError::InvalidParameter)?;
+            // from_str_radix would also take a sign, as in "+f".
+            if part.len() != 2 || !part.bytes().all(|b| b.is_ascii_hexdigit()) {
+                return Err(DError::InvalidParameter);
+            }
+            *byte = u8::from_str_radix(part, 16).map_err(|_| DError::InvalidParameter)?;
+        }
+        if parts.next().is_some() {
+            return Err(DError::InvalidParameter);
+        }
+        Ok(MacAddr6(bytes))
+    }
+}
+
 impl From<[u8; 6]> for MacAddr6 {
     fn from(addr: [u8; 6]) -> Self {
         MacAddr6(addr)
@@ -523,3 +2478,82 @@ impl From<MacAddr6> for [u8; 6] {
         addr.0
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tx_arbitration_weights_map_to_credit_quanta() {
+        let credits = tx_arbitration_credits(&[1, 2, 4, 255]).unwrap();
+        assert_eq!(credits, [24, 48, 96, 6120]);
+
+        let value =
+            RTTDTCRC::CRQ.val(credits[1]) + RTTDTCRC::MCL.val(credits[1] * TX_CREDIT_LIMIT_FACTOR);
+        assert_eq!(value.value, 48 | (96 << 14));
+        // The largest weight still fits both fields.
+        assert!(credits[3] <= RTTDTCRC::CRQ.mask);
+        assert!(credits[3] * TX_CREDIT_LIMIT_FACTOR <= RTTDTCRC::MCL.mask);
+    }
+
+    #[test]
+    fn tx_arbitration_rejects_bad_weights() {
+        assert!(tx_arbitration_credits(&[1, 1, 1]).is_err());
+        assert!(tx_arbitration_credits(&[1, 1, 1, 1, 1]).is_err());
+        assert!(tx_arbitration_credits(&[1, 0, 1, 1]).is_err());
+    }
+
+    #[test]
+    fn parses_mac_address_in_either_case() {
+        let expected = MacAddr6::new([0x00, 0x1b, 0x21, 0xab, 0xcd, 0xef]);
+        assert_eq!("00:1b:21:ab:cd:ef".parse::<MacAddr6>().unwrap(), expected);
+        assert_eq!("00:1B:21:AB:CD:EF".parse::<MacAddr6>().unwrap(), expected);
+        assert_eq!("00-1b-21-Ab-cD-eF".parse::<MacAddr6>().unwrap(), expected);
+    }
+
+    #[test]
+    fn rejects_malformed_mac_addresses() {
+        for s in [
+            // Wrong octet count.
+            "00:1b:21:ab:cd",
+            "00:1b:21:ab:cd:ef:01",
+            // Non-hex octet.
+            "00:1b:21:ag:cd:ef",
+            // Over-long octet.
+            "00:1b:21:abc:cd:ef",
+            // Empty input.
+            "",
+            // Trailing separator.
+            "00:1b:21:ab:cd:ef:",
+            // Mixed separators.
+            "00:1b-21:ab:cd:ef",
+        ] {
+            assert!(s.parse::<MacAddr6>().is_err(), "{s:?}");
+        }
+    }
+
+    #[test]
+    fn interrupt_summary_yields_exactly_the_set_causes() {
+        let summary = InterruptSummary {
+            tx_descriptor_written: true,
+            link_status_change: true,
+            rx_timer: true,
+            asserted: true,
+            ..Default::default()
+        };
+        let causes: Vec<_> = summary.iter().collect();
+        assert_eq!(
+            causes,
+            [
+                Cause::TxDescriptorWritten,
+                Cause::LinkStatusChange,
+                Cause::RxTimer
+            ]
+        );
+    }
+
+    #[test]
+    fn empty_interrupt_summary_yields_nothing() {
+        assert_eq!(InterruptSummary::default().iter().next(), None);
+    }
+}
@@ -1,4 +1,4 @@
-use core::time::Duration;
+use core::{ptr::NonNull, time::Duration};
 
 use trait_ffi::def_extern_trait;
 
@@ -8,18 +8,87 @@ pub enum DError {
     Unknown(&'static str),
     #[error("Operation timed out")]
     Timeout,
+    #[error("Timed out waiting for {0}")]
+    TimeoutAt(&'static str),
+    #[error("Link partner shares no speed with the local advertisement")]
+    NoCommonSpeed,
     #[error("No memory available")]
     NoMemory,
     #[error("Invalid parameter")]
     InvalidParameter,
+    #[error("Resource busy")]
+    Busy,
 }
 
 #[def_extern_trait]
 pub trait Kernel {
     fn sleep(duration: Duration);
+    /// Monotonic time since an arbitrary fixed point, e.g. boot.
+    fn now() -> Duration;
+    /// Allocates `size` bytes of zeroed, physically contiguous memory whose
+    /// bus address is aligned to `align`, for descriptor rings and packet
+    /// buffers. `None` when out of memory.
+    fn dma_alloc(size: usize, align: usize) -> Option<DmaRegion>;
+    /// Returns memory from [`Kernel::dma_alloc`] or otherwise handed to the
+    /// crate, e.g. the descriptor memory of a freed ring.
+    fn dma_dealloc(region: DmaRegion);
 }
 
+/// A physically contiguous memory region the device can DMA into.
+///
+/// `virt` is the CPU view of the memory, `bus` the address the device
+/// must be programmed with.
+#[derive(Debug, Clone, Copy)]
+pub struct DmaRegion {
+    pub virt: NonNull<u8>,
+    pub bus: u64,
+    pub size: usize,
+}
+
+impl DmaRegion {
+    /// Checks that the region holds at least `size` bytes and that its bus
+    /// address is aligned to `align`.
+    pub(crate) fn check(&self, size: usize, align: usize) -> Result<(), DError> {
+        if self.size < size || !self.bus.is_multiple_of(align as u64) {
+            return Err(DError::InvalidParameter);
+        }
+        Ok(())
+    }
+}
+
+/// Bus addresses the device is allowed to use for DMA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DmaMask {
+    /// Only memory below 4GB is reachable, the high address registers stay 0.
+    Bits32,
+    #[default]
+    Bits64,
+}
+
+impl DmaMask {
+    pub fn max_addr(&self) -> u64 {
+        match self {
+            DmaMask::Bits32 => u32::MAX as u64,
+            DmaMask::Bits64 => u64::MAX,
+        }
+    }
+
+    /// Returns whether the whole `region` is reachable under this mask.
+    pub fn contains(&self, region: &DmaRegion) -> bool {
+        match (region.size as u64).checked_sub(1) {
+            Some(last) => region
+                .bus
+                .checked_add(last)
+                .is_some_and(|end| end <= self.max_addr()),
+            None => region.bus <= self.max_addr(),
+        }
+    }
+}
+
+/// Polls `f` until it returns true. On timeout the error names `what` was
+/// being waited for, e.g. the register bit.
 pub(crate) fn wait_for<F: FnMut() -> bool>(
+    what: &'static str,
     mut f: F,
     interval: Duration,
     try_count: Option<usize>,
@@ -31,5 +100,28 @@ pub(crate) fn wait_for<F: FnMut() -> bool>(
 
         kernel::sleep(interval);
     }
-    Err(DError::Timeout)
+    Err(DError::TimeoutAt(what))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn region(bus: u64, size: usize) -> DmaRegion {
+        DmaRegion {
+            virt: NonNull::dangling(),
+            bus,
+            size,
+        }
+    }
+
+    #[test]
+    fn bits32_rejects_regions_above_4gb() {
+        assert!(DmaMask::Bits32.contains(&region(0x1000, 0x1000)));
+        assert!(DmaMask::Bits32.contains(&region(0xffff_f000, 0x1000)));
+        assert!(!DmaMask::Bits32.contains(&region(0x1_0000_0000, 0x1000)));
+        // Starts below 4GB but its last byte is above.
+        assert!(!DmaMask::Bits32.contains(&region(0xffff_f000, 0x1001)));
+        assert!(DmaMask::Bits64.contains(&region(0x1_0000_0000, 0x1000)));
+    }
 }
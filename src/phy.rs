@@ -1,12 +1,68 @@
-use core::cell::RefCell;
+use core::{cell::RefCell, time::Duration};
 
-use log::debug;
-use tock_registers::register_bitfields;
+use log::warn;
+use tock_registers::{register_bitfields, LocalRegisterCopy};
 
-use crate::{mac::Mac, osal::*};
+use crate::{
+    mac::{Mac, Speed},
+    osal::*,
+};
+
+/// Time [`Phy::wait_for_auto_negotiation_complete`] waits.
+pub const DEFAULT_AUTONEG_TIMEOUT: Duration = Duration::from_secs(3);
 
 const PHY_CONTROL: u32 = 0;
 const PHY_STATUS: u32 = 1;
+const PHY_AUTONEG_ADV: u32 = 4;
+const PHY_LP_ABILITY: u32 = 5;
+const PHY_1000T_CTRL: u32 = 9;
+const PHY_1000T_STATUS: u32 = 10;
+const PHY_SPECIFIC_STATUS: u32 = 17;
+const PHY_PAGE_SELECT: u32 = 22;
+const PHY_ID1: u32 = 2;
+
+/// Organizationally unique identifier bits of PHY identifier 1 for Marvell,
+/// whose 88E1xxx PHYs carry the die temperature sensor.
+const PHY_ID1_MARVELL: u16 = 0x0141;
+/// Temperature sensor register, page 6 register 27 on the 88E1510 family.
+const PHY_TEMP_PAGE: u16 = 6;
+const PHY_TEMP_SENSOR: u32 = 27;
+
+/// 10/100 half/full duplex ability bits in the advertisement and link
+/// partner ability registers.
+const NWAY_10_100_MASK: u16 = 0b1111 << 5;
+/// PAUSE and ASM_DIR bits in the advertisement and link partner ability
+/// registers.
+const NWAY_PAUSE: u16 = 1 << 10;
+const NWAY_ASM_DIR: u16 = 1 << 11;
+/// 1000BASE-T half/full duplex in the 1000BASE-T control register, the link
+/// partner's sit two bits higher in the 1000BASE-T status register.
+const CR_1000T_MASK: u16 = 0b11 << 8;
+const SR_1000T_LP_MASK: u16 = 0b11 << 10;
+/// Clause 22 registers used to reach the clause 45 MMD registers.
+const PHY_MMD_ACCESS_CONTROL: u32 = 13;
+const PHY_MMD_ADDRESS_DATA: u32 = 14;
+
+/// MMD access control function: address, or data without post increment.
+const MMD_FUNC_ADDRESS: u16 = 0;
+const MMD_FUNC_DATA: u16 = 1 << 14;
+
+/// PCS device and its status 1 register (3.1).
+const MMD_PCS: u8 = 3;
+const MMD_PCS_STATUS1: u16 = 1;
+/// Auto-negotiation device with the local (7.60) and link partner (7.61) EEE
+/// advertisement.
+const MMD_AN: u8 = 7;
+const MMD_AN_EEE_ADV: u16 = 60;
+const MMD_AN_EEE_LP_ADV: u16 = 61;
+
+/// EEE advertisement bits for 100BASE-TX and 1000BASE-T.
+const EEE_100TX: u16 = 1 << 1;
+const EEE_1000T: u16 = 1 << 2;
+
+/// PCS status 1 LPI bits.
+const PCS_RX_LPI_INDICATION: u16 = 1 << 8;
+const PCS_TX_LPI_INDICATION: u16 = 1 << 9;
 
 register_bitfields! {
     u16,
@@ -247,6 +303,58 @@ register_bitfields! {
     ]
 }
 
+register_bitfields! {
+    u16,
+
+    /// Copper Specific Status Register 1 (PSSR) - Register 17, page 0
+    /// Reports the speed and duplex resolved by auto-negotiation.
+    PSSR [
+        /// Speed
+        /// 10b = 1000 Mb/s
+        /// 01b = 100 Mb/s
+        /// 00b = 10 Mb/s
+        /// Only valid once SPEED_DUPLEX_RESOLVED is set.
+        SPEED OFFSET(14) NUMBITS(2) [
+            Speed10 = 0,
+            Speed100 = 1,
+            Speed1000 = 0b10
+        ],
+
+        /// Duplex
+        /// 1b = Full duplex
+        /// 0b = Half duplex
+        /// Only valid once SPEED_DUPLEX_RESOLVED is set.
+        DUPLEX OFFSET(13) NUMBITS(1) [
+            Half = 0,
+            Full = 1
+        ],
+
+        /// Speed and Duplex Resolved
+        /// 1b = Resolved
+        /// 0b = Not resolved
+        SPEED_DUPLEX_RESOLVED OFFSET(11) NUMBITS(1) [],
+
+        /// Copper Link (real time)
+        /// 1b = Link up
+        /// 0b = Link down
+        LINK OFFSET(10) NUMBITS(1) []
+    ]
+}
+
+/// Pause resolution of IEEE 802.3 table 28B-3, returns (send, receive).
+fn resolve_pause(local: u16, partner: u16) -> (bool, bool) {
+    let bits = |reg: u16| (reg & NWAY_PAUSE != 0, reg & NWAY_ASM_DIR != 0);
+    match (bits(local), bits(partner)) {
+        // Both sides pause symmetrically.
+        ((true, _), (true, _)) => (true, true),
+        // We only send pause frames, the partner only honors them.
+        ((false, true), (true, true)) => (true, false),
+        // We only honor pause frames the partner sends.
+        ((true, true), (false, true)) => (false, true),
+        _ => (false, false),
+    }
+}
+
 pub struct Phy {
     mac: RefCell<Mac>,
     addr: u32,
@@ -257,12 +365,106 @@ impl Phy {
         Self { mac, addr: 1 }
     }
 
-    pub fn read_mdic(&mut self, offset: u32) -> Result<u16, DError> {
-        self.mac.borrow_mut().read_mdic(self.addr, offset)
+    /// MDIO address of the internal PHY.
+    pub fn addr(&self) -> u32 {
+        self.addr
+    }
+
+    pub fn read_mdic(&self, offset: u32) -> Result<u16, DError> {
+        self.mac.borrow().read_mdic(self.addr, offset)
     }
 
     pub fn write_mdic(&mut self, offset: u32, data: u16) -> Result<(), DError> {
-        self.mac.borrow_mut().write_mdic(self.addr, offset, data)
+        let mac = self.mac.borrow();
+        mac.write_mdic(self.addr, offset, data)?;
+        if mac.verify_writes() {
+            // Reset and restart auto-negotiation clear themselves.
+            let mask = match offset {
+                PHY_CONTROL => !(PCTRL::RESET::SET + PCTRL::RESTART_AUTO_NEGOTIATION::SET).value,
+                _ => u16::MAX,
+            };
+            let read = mac.read_mdic(self.addr, offset)?;
+            if (read ^ data) & mask != 0 {
+                warn!(
+                    "PHY register {} write not applied: wrote {:#06x}, read back {:#06x}",
+                    offset, data, read
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Verifies PHY register writes by reading them back, see
+    /// [`Mac::set_verify_writes`].
+    pub fn set_verify_writes(&mut self, enable: bool) {
+        self.mac.borrow_mut().set_verify_writes(enable);
+    }
+
+    /// Reads register `reg` of MMD device `devad` through the clause 22 MMD
+    /// access registers.
+    pub fn read_mmd(&self, devad: u8, reg: u16) -> Result<u16, DError> {
+        let mac = self.mac.borrow();
+        let devad = (devad & 0x1f) as u16;
+        mac.write_mdic(self.addr, PHY_MMD_ACCESS_CONTROL, MMD_FUNC_ADDRESS | devad)?;
+        mac.write_mdic(self.addr, PHY_MMD_ADDRESS_DATA, reg)?;
+        mac.write_mdic(self.addr, PHY_MMD_ACCESS_CONTROL, MMD_FUNC_DATA | devad)?;
+        mac.read_mdic(self.addr, PHY_MMD_ADDRESS_DATA)
+    }
+
+    /// Reports what EEE was negotiated with the link partner and whether the
+    /// link is in low-power idle right now.
+    pub fn eee_status(&self) -> Result<EeeStatus, DError> {
+        let adv = self.read_mmd(MMD_AN, MMD_AN_EEE_ADV)?;
+        let lp_adv = self.read_mmd(MMD_AN, MMD_AN_EEE_LP_ADV)?;
+        let pcs = self.read_mmd(MMD_PCS, MMD_PCS_STATUS1)?;
+        let negotiated = adv & lp_adv;
+
+        Ok(EeeStatus {
+            negotiated_100: negotiated & EEE_100TX != 0,
+            negotiated_1000: negotiated & EEE_1000T != 0,
+            rx_lpi: pcs & PCS_RX_LPI_INDICATION != 0,
+            tx_lpi: pcs & PCS_TX_LPI_INDICATION != 0,
+        })
+    }
+
+    /// Reads MII registers 0 to 31 of the current page for a diagnostic dump.
+    /// The PHY semaphore is held for the whole dump, so management firmware
+    /// cannot switch pages in between.
+    pub fn dump_registers(&self) -> Result<[u16; 32], DError> {
+        let mac = self.mac.borrow();
+        mac.acquire_phy()?;
+        let mut regs = [0; 32];
+        let res = regs.iter_mut().enumerate().try_for_each(|(offset, reg)| {
+            *reg = mac.read_mdic(self.addr, offset as u32)?;
+            Ok(())
+        });
+        mac.release_phy();
+        res.map(|_| regs)
+    }
+
+    /// Reads register `offset` of page `page`, restoring page 0 afterwards.
+    /// The PHY semaphore is held while the page is switched away.
+    pub fn read_paged(&self, page: u16, offset: u32) -> Result<u16, DError> {
+        let mac = self.mac.borrow();
+        mac.acquire_phy()?;
+        let res = mac
+            .write_mdic(self.addr, PHY_PAGE_SELECT, page)
+            .and_then(|_| mac.read_mdic(self.addr, offset));
+        let restore = mac.write_mdic(self.addr, PHY_PAGE_SELECT, 0);
+        mac.release_phy();
+        let val = res?;
+        restore.map(|_| val)
+    }
+
+    /// Die temperature of the PHY in degrees Celsius, independent of the MAC
+    /// thermal sensor. Only PHYs with the Marvell sensor at page 6 register 27
+    /// have one, anything else is rejected with [`DError::InvalidParameter`].
+    pub fn die_temperature(&self) -> Result<i16, DError> {
+        if self.read_mdic(PHY_ID1)? != PHY_ID1_MARVELL {
+            return Err(DError::InvalidParameter);
+        }
+        let raw = self.read_paged(PHY_TEMP_PAGE, PHY_TEMP_SENSOR)?;
+        Ok(decode_die_temperature(raw))
     }
 
     pub fn power_up(&mut self) -> Result<(), DError> {
@@ -271,26 +473,140 @@ impl Phy {
         self.write_mdic(PHY_CONTROL, mii_reg)
     }
 
-    pub fn read_status(&mut self) -> Result<u16, DError> {
+    /// Powers the PHY down, dropping the link. Use [`Phy::power_up`] to bring
+    /// it back.
+    pub fn power_down(&mut self) -> Result<(), DError> {
+        let mut mii_reg = self.read_mdic(PHY_CONTROL)?;
+        mii_reg |= PCTRL::POWER_DOWN::SET.value;
+        self.write_mdic(PHY_CONTROL, mii_reg)
+    }
+
+    /// Reads and decodes the PHY control register (MII register 0).
+    pub fn control(&self) -> Result<PhyControl, DError> {
+        let reg = LocalRegisterCopy::<u16, PCTRL::Register>::new(self.read_mdic(PHY_CONTROL)?);
+        let speed = match (
+            reg.is_set(PCTRL::SPEED_SELECTION_MSB),
+            reg.is_set(PCTRL::SPEED_SELECTION_LSB),
+        ) {
+            (false, false) => Some(Speed::Mb10),
+            (false, true) => Some(Speed::Mb100),
+            (true, false) => Some(Speed::Mb1000),
+            (true, true) => None,
+        };
+
+        Ok(PhyControl {
+            reset: reg.is_set(PCTRL::RESET),
+            loopback: reg.is_set(PCTRL::LOOPBACK),
+            speed,
+            auto_negotiation: reg.is_set(PCTRL::AUTO_NEGOTIATION_ENABLE),
+            power_down: reg.is_set(PCTRL::POWER_DOWN),
+            isolate: reg.is_set(PCTRL::ISOLATE),
+            restart_auto_negotiation: reg.is_set(PCTRL::RESTART_AUTO_NEGOTIATION),
+            full_duplex: reg.is_set(PCTRL::DUPLEX_MODE),
+            collision_test: reg.is_set(PCTRL::COLLISION_TEST),
+        })
+    }
+
+    /// Returns the duplex resolved by the PHY, `Some(true)` for full duplex, or
+    /// `None` while negotiation has not resolved it yet. The PHY is
+    /// authoritative here while the MAC's STATUS.FD may still be updating.
+    pub fn resolved_duplex(&self) -> Result<Option<bool>, DError> {
+        let pssr =
+            LocalRegisterCopy::<u16, PSSR::Register>::new(self.read_mdic(PHY_SPECIFIC_STATUS)?);
+        if !pssr.is_set(PSSR::SPEED_DUPLEX_RESOLVED) {
+            return Ok(None);
+        }
+        Ok(Some(pssr.is_set(PSSR::DUPLEX)))
+    }
+
+    /// Resolves the pause directions from our and the link partner's PAUSE
+    /// and ASM_DIR advertisement, returning (can_send_pause,
+    /// can_receive_pause). Only meaningful once auto-negotiation completed.
+    pub fn asymmetric_pause_resolved(&self) -> Result<(bool, bool), DError> {
+        let local = self.read_mdic(PHY_AUTONEG_ADV)?;
+        let partner = self.read_mdic(PHY_LP_ABILITY)?;
+        Ok(resolve_pause(local, partner))
+    }
+
+    pub fn read_status(&self) -> Result<u16, DError> {
         self.read_mdic(PHY_STATUS)
     }
 
     pub fn wait_for_auto_negotiation_complete(&mut self) -> Result<(), DError> {
-        let interval = core::time::Duration::from_millis(100);
-        let try_count = 30; // Wait for up to 3 seconds
+        self.wait_auto_negotiation(DEFAULT_AUTONEG_TIMEOUT)
+    }
+
+    /// Waits up to `timeout` for the PHY to finish auto-negotiation. This is
+    /// only the PHY phase, the MAC may report link up later, see
+    /// [`Mac::wait_link_up`].
+    pub fn wait_auto_negotiation(&self, timeout: Duration) -> Result<(), DError> {
+        let interval = Duration::from_millis(100);
 
-        wait_for(
+        let res = wait_for(
+            "PHY auto-negotiation complete",
             || self.is_auto_negotiation_complete().unwrap_or(false),
             interval,
-            Some(try_count),
-        )
+            Some((timeout.as_millis() / interval.as_millis()).max(1) as usize),
+        );
+        if res.is_err() && self.no_common_speed().unwrap_or(false) {
+            return Err(DError::NoCommonSpeed);
+        }
+        res
+    }
+
+    /// Whether the link partner advertised abilities, but none of them
+    /// matches what we advertise. Negotiation can never complete then.
+    fn no_common_speed(&self) -> Result<bool, DError> {
+        let local = (self.read_mdic(PHY_AUTONEG_ADV)? & NWAY_10_100_MASK) as u32
+            | ((self.read_mdic(PHY_1000T_CTRL)? & CR_1000T_MASK) as u32) << 8;
+        let partner = (self.read_mdic(PHY_LP_ABILITY)? & NWAY_10_100_MASK) as u32
+            | ((self.read_mdic(PHY_1000T_STATUS)? & SR_1000T_LP_MASK) as u32) << 6;
+        if partner == 0 {
+            // Nothing received from the partner, e.g. no cable.
+            return Ok(false);
+        }
+        if local & partner == 0 {
+            warn!(
+                "no common speed: local advertises {:#x}, partner {:#x}",
+                local, partner
+            );
+            return Ok(true);
+        }
+        Ok(false)
     }
 
-    pub fn is_auto_negotiation_complete(&mut self) -> Result<bool, DError> {
+    pub fn is_auto_negotiation_complete(&self) -> Result<bool, DError> {
         let status = self.read_status()?;
         Ok(status & PSTATUS::AUTO_NEGOTIATION_COMPLETE::Complete.value != 0)
     }
 
+    /// Disables auto-negotiation and forces the link to `speed` and duplex.
+    /// 1000BASE-T cannot be forced, it requires auto-negotiation.
+    pub fn force_speed_duplex(&mut self, speed: Speed, full_duplex: bool) -> Result<(), DError> {
+        let mut control =
+            LocalRegisterCopy::<u16, PCTRL::Register>::new(self.read_mdic(PHY_CONTROL)?);
+        let (msb, lsb) = match speed {
+            Speed::Mb1000 => return Err(DError::InvalidParameter),
+            Speed::Mb100 => (0, 1),
+            Speed::Mb10 => (0, 0),
+        };
+        control.modify(
+            PCTRL::AUTO_NEGOTIATION_ENABLE::CLEAR
+                + PCTRL::SPEED_SELECTION_MSB.val(msb)
+                + PCTRL::SPEED_SELECTION_LSB.val(lsb)
+                + PCTRL::DUPLEX_MODE.val(full_duplex as _),
+        );
+        self.write_mdic(PHY_CONTROL, control.get())
+    }
+
+    /// Clears the auto-negotiation enable bit so the PHY stays at the speed
+    /// and duplex selected in its control register.
+    pub fn disable_auto_negotiation(&mut self) -> Result<(), DError> {
+        let mut control = self.read_mdic(PHY_CONTROL)?;
+        control &= !PCTRL::AUTO_NEGOTIATION_ENABLE::Enable.value;
+        self.write_mdic(PHY_CONTROL, control)
+    }
+
     pub fn enable_auto_negotiation(&mut self) -> Result<(), DError> {
         let mut control = self.read_mdic(PHY_CONTROL)?;
         control |= PCTRL::AUTO_NEGOTIATION_ENABLE::Enable.value
@@ -298,3 +614,117 @@ impl Phy {
         self.write_mdic(PHY_CONTROL, control)
     }
 }
+
+/// How [`crate::Igb::open_with`] brings up the link.
+#[derive(Debug, Clone, Copy, Default)]
+pub enum LinkSetup {
+    /// Advertise all speeds and let auto-negotiation pick one.
+    #[default]
+    AutoNeg,
+    /// Force 10 or 100 Mb/s with auto-negotiation disabled.
+    Forced { speed: Speed, full_duplex: bool },
+}
+
+/// Decoded PHY control register, see [`Phy::control`].
+#[derive(Debug, Clone, Copy)]
+pub struct PhyControl {
+    pub reset: bool,
+    pub loopback: bool,
+    /// Forced speed, `None` for the reserved encoding. Ignored while
+    /// auto-negotiation is enabled.
+    pub speed: Option<Speed>,
+    pub auto_negotiation: bool,
+    pub power_down: bool,
+    pub isolate: bool,
+    pub restart_auto_negotiation: bool,
+    /// Forced duplex, ignored while auto-negotiation is enabled.
+    pub full_duplex: bool,
+    pub collision_test: bool,
+}
+
+/// EEE state of the link, see [`Phy::eee_status`].
+#[derive(Debug, Clone, Copy)]
+pub struct EeeStatus {
+    /// Both sides advertise EEE for 100BASE-TX.
+    pub negotiated_100: bool,
+    /// Both sides advertise EEE for 1000BASE-T.
+    pub negotiated_1000: bool,
+    /// The receive path is currently in low-power idle.
+    pub rx_lpi: bool,
+    /// The transmit path is currently in low-power idle.
+    pub tx_lpi: bool,
+}
+
+impl EeeStatus {
+    pub fn negotiated(&self) -> bool {
+        self.negotiated_100 || self.negotiated_1000
+    }
+}
+
+/// Converts the temperature sensor register: bits 7:0 hold the temperature
+/// offset by 25, so 0 reads as -25 °C and 0xff as 230 °C.
+fn decode_die_temperature(raw: u16) -> i16 {
+    (raw & 0xff) as i16 - 25
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Builds an advertisement word from the PAUSE and ASM_DIR bits.
+    fn adv(pause: bool, asm_dir: bool) -> u16 {
+        (if pause { NWAY_PAUSE } else { 0 }) | (if asm_dir { NWAY_ASM_DIR } else { 0 })
+    }
+
+    #[test]
+    fn pause_resolution_follows_annex_28b() {
+        // (local PAUSE, local ASM_DIR, partner PAUSE, partner ASM_DIR) ->
+        // (send, receive), every row of table 28B-3.
+        #[rustfmt::skip]
+        let table = [
+            ((false, false, false, false), (false, false)),
+            ((false, false, false, true), (false, false)),
+            ((false, false, true, false), (false, false)),
+            ((false, false, true, true), (false, false)),
+            ((false, true, false, false), (false, false)),
+            ((false, true, false, true), (false, false)),
+            ((false, true, true, false), (false, false)),
+            ((false, true, true, true), (true, false)),
+            ((true, false, false, false), (false, false)),
+            ((true, false, false, true), (false, false)),
+            ((true, false, true, false), (true, true)),
+            ((true, false, true, true), (true, true)),
+            ((true, true, false, false), (false, false)),
+            ((true, true, false, true), (false, true)),
+            ((true, true, true, false), (true, true)),
+            ((true, true, true, true), (true, true)),
+        ];
+        for ((lp, la, pp, pa), expected) in table {
+            assert_eq!(
+                resolve_pause(adv(lp, la), adv(pp, pa)),
+                expected,
+                "local {lp}/{la}, partner {pp}/{pa}"
+            );
+        }
+    }
+
+    #[test]
+    fn pause_resolution_ignores_other_ability_bits() {
+        let other = NWAY_10_100_MASK;
+        assert_eq!(
+            resolve_pause(adv(true, false) | other, adv(true, false) | other),
+            (true, true)
+        );
+        assert_eq!(resolve_pause(other, other), (false, false));
+    }
+
+    #[test]
+    fn die_temperature_decoding() {
+        assert_eq!(decode_die_temperature(0), -25);
+        assert_eq!(decode_die_temperature(25), 0);
+        assert_eq!(decode_die_temperature(75), 50);
+        assert_eq!(decode_die_temperature(0xff), 230);
+        // Upper byte is reserved and must not leak into the reading.
+        assert_eq!(decode_die_temperature(0xab00 | 75), 50);
+    }
+}
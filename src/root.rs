@@ -1,15 +1,19 @@
 use alloc::vec::Vec;
 use log::error;
-use pci_types::{CommandRegister, ConfigRegionAccess, PciHeader, StatusRegister};
+use pci_types::{BarWriteError, CommandRegister, ConfigRegionAccess, PciHeader, StatusRegister};
 
 use crate::{
-    BarAllocator, BarHeader, CardBusBridge, Chip, Endpoint, Header, PciAddress, PciPciBridge,
-    SimpleBarAllocator, Unknown,
+    BarAllocator, BarHeader, BarVec, CardBusBridge, Chip, DError, Endpoint, Header, Igb,
+    PciAddress, PciPciBridge, SimpleBarAllocator, Unknown,
 };
 use core::{fmt::Display, hint::spin_loop, ops::Range, ptr::NonNull};
 
 const MAX_DEVICE: u8 = 31;
 const MAX_FUNCTION: u8 = 7;
+/// Bridges nested deeper than this are reported but not scanned behind. Bus
+/// numbers are handed out in increasing order, so a bus is never visited
+/// twice; the limit only bounds the walk on broken hierarchies.
+const MAX_BRIDGE_DEPTH: usize = 32;
 
 pub struct RootComplex<C: Chip> {
     pub(crate) chip: C,
@@ -29,7 +33,7 @@ where
         range: Option<Range<usize>>,
         bar_alloc: Option<A>,
     ) -> PciIterator<'_, C, A> {
-        let range = range.unwrap_or_else(|| 0..0x100);
+        let range = range.unwrap_or(0..0x100);
 
         PciIterator {
             root: self,
@@ -39,6 +43,8 @@ where
             function: 0,
             is_mulitple_function: false,
             is_finish: false,
+            descend_non_transparent: false,
+            bar_mode: BarMode::Assign,
             stack: alloc::vec![Bridge::root(range.start as _)],
         }
     }
@@ -60,6 +66,94 @@ where
         self.__enumerate(range, None)
     }
 
+    /// Finds the first endpoint accepted by `matches(vendor_id, device_id)`
+    /// that has a memory BAR0, enables memory decoding and bus mastering on
+    /// it and maps its BAR0 with `map(bus_address, size)`.
+    ///
+    /// BARs are kept as assigned, so run an allocating `enumerate` first if
+    /// firmware did not set them up.
+    pub fn find_and_map<F, M>(&mut self, matches: F, map: M) -> Option<MappedDevice>
+    where
+        F: Fn(u16, u16) -> bool,
+        M: Fn(usize, usize) -> NonNull<u8>,
+    {
+        for elem in self.enumerate_keep_bar(None) {
+            let Header::Endpoint(endpoint) = elem.header else {
+                continue;
+            };
+            if !matches(endpoint.vendor_id, endpoint.device_id) {
+                continue;
+            }
+
+            // A match without a memory BAR0 cannot be mapped, keep looking.
+            let bar0 = match &endpoint.bar {
+                BarVec::Memory32(bar_vec) => bar_vec[0]
+                    .as_ref()
+                    .map(|bar0| (bar0.address as usize, bar0.size as usize)),
+                BarVec::Memory64(bar_vec) => bar_vec[0]
+                    .as_ref()
+                    .map(|bar0| (bar0.address as usize, bar0.size as usize)),
+                BarVec::Io(_) => None,
+            };
+            let Some((bar_addr, bar_size)) = bar0 else {
+                continue;
+            };
+
+            endpoint.update_command(elem.root, |cmd| {
+                cmd | CommandRegister::MEMORY_ENABLE | CommandRegister::BUS_MASTER_ENABLE
+            });
+
+            return Some(MappedDevice {
+                bar0: map(bar_addr, bar_size),
+                bar0_size: bar_size,
+                endpoint,
+            });
+        }
+        None
+    }
+
+    /// Discovery pass of a two-phase enumeration: lists every endpoint with
+    /// its BARs as currently programmed, without touching them. Bridges still
+    /// get bus numbers assigned, scanning needs them. Follow with
+    /// [`RootComplex::assign_bars`].
+    pub fn probe(&mut self) -> Vec<ProbedDevice> {
+        self.enumerate_keep_bar(None)
+            .filter_map(|elem| match elem.header {
+                Header::Endpoint(endpoint) => Some(ProbedDevice { endpoint }),
+                _ => None,
+            })
+            .collect()
+    }
+
+    /// Assignment pass: gives every memory BAR of `devices` space from
+    /// `allocator` and refreshes their BARs. Memory decoding stays off until
+    /// the caller enables it, e.g. via [`Endpoint::update_command`].
+    pub fn assign_bars<A: BarAllocator>(
+        &mut self,
+        devices: &mut [ProbedDevice],
+        allocator: &mut A,
+    ) -> Result<(), DError> {
+        for dev in devices {
+            let ep = &mut dev.endpoint;
+            let mut header =
+                pci_types::EndpointHeader::from_header(PciHeader::new(ep.address), &*self)
+                    .ok_or(DError::InvalidParameter)?;
+            ep.bar = allocate_bars(self, &mut header, &ep.bar, allocator)?;
+        }
+        Ok(())
+    }
+
+    /// Returns every endpoint listed in [`Igb::SUPPORTED_DEVICES`], so all
+    /// ports of a multi-NIC system can be brought up.
+    pub fn find_supported_nics(&mut self) -> Vec<EnumElem<'_, C>> {
+        self.enumerate_keep_bar(None)
+            .filter(|elem| match &elem.header {
+                Header::Endpoint(ep) => Igb::check_vid_did(ep.vendor_id, ep.device_id),
+                _ => false,
+            })
+            .collect()
+    }
+
     pub fn read_config(&self, address: PciAddress, offset: u16) -> u32 {
         unsafe { self.chip.read(self.mmio_base, address, offset) }
     }
@@ -67,6 +161,26 @@ where
     pub fn write_config(&mut self, address: PciAddress, offset: u16, value: u32) {
         unsafe { self.chip.write(self.mmio_base, address, offset, value) }
     }
+
+    /// Reads a `u8`, `u16` or `u32` config register at `offset`, which must
+    /// be aligned to its size. The containing dword is read and the value
+    /// shifted out of it.
+    pub fn read_config_as<T: ConfigValue>(&self, address: PciAddress, offset: u16) -> T {
+        assert_config_aligned::<T>(offset);
+        let dword = self.read_config(address, offset & !0b11);
+        T::extract(dword, offset & 0b11)
+    }
+
+    /// Writes a `u8`, `u16` or `u32` config register at `offset`, aligned to
+    /// its size. Narrow writes read-modify-write the containing dword, so
+    /// they must not share one with write-1-to-clear status bits.
+    pub fn write_config_as<T: ConfigValue>(&mut self, address: PciAddress, offset: u16, value: T) {
+        assert_config_aligned::<T>(offset);
+        let aligned = offset & !0b11;
+        let dword = self.read_config(address, aligned);
+        let dword = value.insert(dword, offset & 0b11);
+        self.write_config(address, aligned, dword);
+    }
 }
 
 impl<C: Chip> ConfigRegionAccess for RootComplex<C> {
@@ -89,6 +203,41 @@ impl<C: Chip> ConfigRegionAccess for &mut RootComplex<C> {
     }
 }
 
+/// Config register widths usable with [`RootComplex::read_config_as`].
+pub trait ConfigValue: Copy {
+    /// Takes the value at byte `shift` out of `dword`.
+    fn extract(dword: u32, shift: u16) -> Self;
+    /// Replaces the bytes at `shift` in `dword` with `self`.
+    fn insert(self, dword: u32, shift: u16) -> u32;
+}
+
+macro_rules! impl_config_value {
+    ($($ty:ty),*) => {$(
+        impl ConfigValue for $ty {
+            fn extract(dword: u32, shift: u16) -> Self {
+                (dword >> (shift * 8)) as $ty
+            }
+
+            fn insert(self, dword: u32, shift: u16) -> u32 {
+                let mask = (<$ty>::MAX as u32) << (shift * 8);
+                (dword & !mask) | ((self as u32) << (shift * 8))
+            }
+        }
+    )*};
+}
+
+impl_config_value!(u8, u16, u32);
+
+/// A misaligned offset would straddle two dwords or pick the wrong bytes.
+fn assert_config_aligned<T: ConfigValue>(offset: u16) {
+    assert!(
+        offset.is_multiple_of(size_of::<T>() as u16),
+        "config offset {:#x} not aligned to {} bytes",
+        offset,
+        size_of::<T>()
+    );
+}
+
 pub struct PciIterator<'a, C: Chip, A: BarAllocator> {
     /// This must only be used to read read-only fields, and must not be exposed outside this
     /// module, because it uses the same CAM as the main `PciRoot` instance.
@@ -100,6 +249,20 @@ pub struct PciIterator<'a, C: Chip, A: BarAllocator> {
     function: u8,
     is_mulitple_function: bool,
     is_finish: bool,
+    descend_non_transparent: bool,
+    bar_mode: BarMode,
+}
+
+/// How enumeration treats endpoint BARs, see [`PciIterator::bar_mode`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BarMode {
+    /// Size every BAR by writing all ones, and reprogram it if an allocator
+    /// was passed to [`RootComplex::enumerate`].
+    #[default]
+    Assign,
+    /// Only read the BARs firmware assigned. Nothing is written and decoding
+    /// is never turned off, but the reported sizes are 0.
+    Discover,
 }
 
 impl<'a, C: Chip, A: BarAllocator> Iterator for PciIterator<'a, C, A> {
@@ -109,7 +272,12 @@ impl<'a, C: Chip, A: BarAllocator> Iterator for PciIterator<'a, C, A> {
         while !self.is_finish {
             if let Some(value) = self.get_current_valid() {
                 self.next(match &value {
-                    Header::PciPciBridge(bridge) => Some(bridge),
+                    Header::PciPciBridge(bridge)
+                        if (self.descend_non_transparent || !bridge.is_non_transparent())
+                            && self.stack.len() <= MAX_BRIDGE_DEPTH =>
+                    {
+                        Some(bridge)
+                    }
                     _ => None,
                 });
                 return Some(EnumElem {
@@ -129,6 +297,20 @@ pub struct EnumElem<'a, C: Chip> {
     pub header: Header,
 }
 
+/// An endpoint found by [`RootComplex::probe`].
+#[derive(Debug, Clone)]
+pub struct ProbedDevice {
+    pub endpoint: Endpoint,
+}
+
+/// An endpoint returned by [`RootComplex::find_and_map`].
+pub struct MappedDevice {
+    pub endpoint: Endpoint,
+    /// CPU address BAR0 is mapped at.
+    pub bar0: NonNull<u8>,
+    pub bar0_size: usize,
+}
+
 impl<C: Chip> Display for EnumElem<'_, C> {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(f, "{}", self.header)
@@ -136,6 +318,22 @@ impl<C: Chip> Display for EnumElem<'_, C> {
 }
 
 impl<C: Chip, A: BarAllocator> PciIterator<'_, C, A> {
+    /// Whether to scan behind bridges that look non-transparent (see
+    /// [`PciPciBridge::is_non_transparent`]). Off by default: the bridge
+    /// itself is still reported, but no bus number is spent on it and the
+    /// other side is not scanned.
+    pub fn descend_non_transparent(mut self, descend: bool) -> Self {
+        self.descend_non_transparent = descend;
+        self
+    }
+
+    /// Selects how BARs are read, [`BarMode::Assign`] by default. In
+    /// [`BarMode::Discover`] the allocator is not used.
+    pub fn bar_mode(mut self, mode: BarMode) -> Self {
+        self.bar_mode = mode;
+        self
+    }
+
     fn get_current_valid(&mut self) -> Option<Header> {
         let address = self.address();
 
@@ -159,60 +357,16 @@ impl<C: Chip, A: BarAllocator> PciIterator<'_, C, A> {
                 let access = &self.root;
                 let mut ep = pci_types::EndpointHeader::from_header(pci_header, access).unwrap();
 
-                let mut bar = ep.parse_bar(6, access);
+                let mut bar = match self.bar_mode {
+                    BarMode::Assign => ep.parse_bar(6, access),
+                    BarMode::Discover => ep.discover_bar(6, access),
+                };
                 let (interrupt_pin, interrupt_line) = ep.interrupt(access);
                 let capability_pointer = ep.capability_pointer(access);
                 let capabilities = ep.capabilities(access).collect::<Vec<_>>();
 
-                if let Some(a) = &mut self.allocator {
-                    ep.update_command(access, |mut cmd| {
-                        cmd.remove(CommandRegister::IO_ENABLE);
-                        cmd.remove(CommandRegister::MEMORY_ENABLE);
-                        cmd
-                    });
-
-                    match &bar {
-                        crate::BarVec::Memory32(bar_vec) => {
-                            let new_bar_vec = bar_vec
-                                .iter()
-                                .map(|old| {
-                                    old.clone().map(|ref b| a.alloc_memory32(b.size).unwrap())
-                                })
-                                .collect::<Vec<_>>();
-
-                            for (i, bar) in new_bar_vec.into_iter().enumerate() {
-                                if let Some(value) = bar {
-                                    bar_vec.set(i, value, access).unwrap();
-                                }
-                            }
-                        }
-                        crate::BarVec::Memory64(bar_vec) => {
-                            let new_bar_vec = bar_vec
-                                .iter()
-                                .map(|old| {
-                                    old.clone().map(|ref b| {
-                                        if b.address > 0 && b.address < u32::MAX as u64 {
-                                            a.alloc_memory32(b.size as u32).unwrap() as u64
-                                        } else {
-                                            a.alloc_memory64(b.size).unwrap()
-                                        }
-                                    })
-                                })
-                                .collect::<Vec<_>>();
-
-                            for (i, bar) in new_bar_vec.into_iter().enumerate() {
-                                if let Some(value) = bar {
-                                    bar_vec
-                                        .set(i, value, access)
-                                        .inspect_err(|e| error!("{:?}", e))
-                                        .unwrap();
-                                }
-                            }
-                        }
-                        crate::BarVec::Io(_bar_vec_t) => {}
-                    }
-
-                    bar = ep.parse_bar(6, access);
+                if let (BarMode::Assign, Some(a)) = (self.bar_mode, &mut self.allocator) {
+                    bar = allocate_bars(access, &mut ep, &bar, a).unwrap();
                 }
 
                 Header::Endpoint(Endpoint {
@@ -366,6 +520,87 @@ impl<C: Chip, A: BarAllocator> PciIterator<'_, C, A> {
     }
 }
 
+/// Reprograms every BAR of `ep` with space from `a`, memory and I/O
+/// decoding are left off. Returns the BARs as read back.
+fn allocate_bars<C: Chip, A: BarAllocator>(
+    access: &RootComplex<C>,
+    ep: &mut pci_types::EndpointHeader,
+    bar: &BarVec,
+    a: &mut A,
+) -> Result<BarVec, DError> {
+    ep.update_command(access, |mut cmd| {
+        cmd.remove(CommandRegister::IO_ENABLE);
+        cmd.remove(CommandRegister::MEMORY_ENABLE);
+        cmd
+    });
+
+    match bar {
+        BarVec::Memory32(bar_vec) => {
+            let new_bar_vec = bar_vec
+                .iter()
+                .map(|old| match old {
+                    Some(b) => a.alloc_memory32(b.size).map(Some).ok_or(DError::NoMemory),
+                    None => Ok(None),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for (i, bar) in new_bar_vec.into_iter().enumerate() {
+                if let Some(value) = bar {
+                    bar_vec.set(i, value, access).map_err(bar_write_error)?;
+                }
+            }
+        }
+        BarVec::Memory64(bar_vec) => {
+            let new_bar_vec = bar_vec
+                .iter()
+                .map(|old| match old {
+                    Some(b) => if b.address > 0 && b.address < u32::MAX as u64 {
+                        a.alloc_memory32(b.size as u32).map(|v| v as u64)
+                    } else if b.prefetchable {
+                        a.alloc_memory64_prefetchable(b.size)
+                    } else {
+                        a.alloc_memory64(b.size)
+                    }
+                    .map(Some)
+                    .ok_or(DError::NoMemory),
+                    None => Ok(None),
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+
+            for (i, bar) in new_bar_vec.into_iter().enumerate() {
+                if let Some(value) = bar {
+                    bar_vec.set(i, value, access).map_err(bar_write_error)?;
+                }
+            }
+        }
+        BarVec::Io(bar_vec) => {
+            // Without I/O space the BARs stay as they are, like before I/O
+            // allocation existed; few devices need their I/O BARs.
+            let new_bar_vec = bar_vec
+                .iter()
+                .enumerate()
+                .map(|(i, old)| {
+                    old.as_ref()
+                        .and_then(|_| a.alloc_io(bar_vec.size(i, access)))
+                })
+                .collect::<Vec<_>>();
+
+            for (i, port) in new_bar_vec.into_iter().enumerate() {
+                if let Some(port) = port {
+                    bar_vec.set(i, port, access);
+                }
+            }
+        }
+    }
+
+    Ok(ep.parse_bar(6, access))
+}
+
+fn bar_write_error(e: BarWriteError) -> DError {
+    error!("{:?}", e);
+    DError::Unknown("BAR write failed")
+}
+
 struct Bridge {
     header: PciPciBridge,
     device: u8,
@@ -393,3 +628,30 @@ impl Bridge {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::ConfigValue;
+
+    // Interrupt line 0x0b, pin 0x01, min grant 0x22, max latency 0x33, as
+    // read from offset 0x3C.
+    const DWORD: u32 = 0x3322_010b;
+
+    #[test]
+    fn extracts_bytes_and_words() {
+        assert_eq!(u8::extract(DWORD, 0), 0x0b);
+        assert_eq!(u8::extract(DWORD, 1), 0x01);
+        assert_eq!(u8::extract(DWORD, 3), 0x33);
+        assert_eq!(u16::extract(DWORD, 0), 0x010b);
+        assert_eq!(u16::extract(DWORD, 2), 0x3322);
+        assert_eq!(u32::extract(DWORD, 0), DWORD);
+    }
+
+    #[test]
+    fn inserts_without_touching_neighbours() {
+        assert_eq!(0x0au8.insert(DWORD, 0), 0x3322_010a);
+        assert_eq!(0x44u8.insert(DWORD, 3), 0x4422_010b);
+        assert_eq!(0xbeefu16.insert(DWORD, 2), 0xbeef_010b);
+        assert_eq!(0x1234_5678u32.insert(DWORD, 0), 0x1234_5678);
+    }
+}
@@ -0,0 +1,459 @@
+use core::{mem::size_of, ptr::NonNull};
+
+use alloc::vec::Vec;
+use mbarrier::mb;
+
+use crate::{mac::Mac, osal::*};
+
+/// Descriptor rings must start on a 128 byte boundary and their length must
+/// be a multiple of 128 bytes, i.e. 8 legacy descriptors.
+pub const RING_ALIGN: usize = 128;
+
+/// Legacy receive descriptor.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RxDescriptor {
+    pub addr: u64,
+    pub length: u16,
+    pub checksum: u16,
+    pub status: u8,
+    pub errors: u8,
+    pub special: u16,
+}
+
+bitflags::bitflags! {
+    /// Legacy receive descriptor status bits.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RxStatus: u8 {
+        /// Descriptor done, the hardware has written the descriptor back.
+        const DD = 1 << 0;
+        /// End of packet.
+        const EOP = 1 << 1;
+    }
+
+    /// Legacy receive descriptor error bits.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct RxErrors: u8 {
+        /// CRC or alignment error.
+        const CE = 1 << 0;
+        const SE = 1 << 1;
+        const SEQ = 1 << 2;
+        const CXE = 1 << 4;
+        const TCPE = 1 << 5;
+        const IPE = 1 << 6;
+        const RXE = 1 << 7;
+    }
+}
+
+impl RxDescriptor {
+    pub fn status(&self) -> RxStatus {
+        RxStatus::from_bits_retain(self.status)
+    }
+
+    pub fn errors(&self) -> RxErrors {
+        RxErrors::from_bits_retain(self.errors)
+    }
+
+    /// The frame failed the CRC check. Only seen with RCTL.SBP set, otherwise
+    /// the hardware drops such frames.
+    pub fn crc_error(&self) -> bool {
+        self.errors().contains(RxErrors::CE)
+    }
+}
+
+/// Advanced receive descriptor in write-back format, as the hardware leaves
+/// it with SRRCTL.DESCTYPE set to advanced.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct AdvRxWriteback {
+    /// RSS type (3:0), packet type (16:4), header length (30:21) and SPH.
+    pub info: u32,
+    pub rss_hash: u32,
+    /// Extended status (19:0) and extended errors (31:20).
+    pub status_error: u32,
+    pub length: u16,
+    pub vlan: u16,
+}
+
+/// Packet type field of the advanced write-back descriptor.
+const ADV_PKT_IPV4: u32 = 1 << 0;
+const ADV_PKT_IPV6: u32 = 1 << 2;
+const ADV_PKT_TCP: u32 = 1 << 4;
+const ADV_PKT_UDP: u32 = 1 << 5;
+/// Extended status VP: the frame carried an 802.1Q tag.
+const ADV_STAT_VP: u32 = 1 << 3;
+/// Extended errors CE and RXE: CRC or other data error.
+const ADV_ERR_CE: u32 = 1 << 24;
+const ADV_ERR_RXE: u32 = 1 << 31;
+
+impl AdvRxWriteback {
+    pub fn status(&self) -> RxStatus {
+        RxStatus::from_bits_retain(self.status_error as u8)
+    }
+
+    /// Same as [`RxDescriptor::crc_error`] for the advanced layout.
+    pub fn crc_error(&self) -> bool {
+        self.status_error & (ADV_ERR_CE | ADV_ERR_RXE) != 0
+    }
+
+    /// Protocols the hardware recognized in the frame.
+    pub fn packet_type(&self) -> PacketType {
+        let ptype = (self.info >> 4) & 0x1fff;
+        PacketType {
+            ipv4: ptype & ADV_PKT_IPV4 != 0,
+            ipv6: ptype & ADV_PKT_IPV6 != 0,
+            tcp: ptype & ADV_PKT_TCP != 0,
+            udp: ptype & ADV_PKT_UDP != 0,
+            vlan: self.status_error & ADV_STAT_VP != 0,
+        }
+    }
+}
+
+/// Protocols of a received frame as classified by the hardware, letting a
+/// stack dispatch without parsing headers.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PacketType {
+    pub ipv4: bool,
+    pub ipv6: bool,
+    pub tcp: bool,
+    pub udp: bool,
+    pub vlan: bool,
+}
+
+/// Buffer size the ring hands to the hardware unless configured otherwise.
+pub const DEFAULT_RX_BUFFER_SIZE: usize = 2048;
+/// Largest buffer an advanced descriptor ring can use, legacy rings stop at
+/// 2048 bytes.
+pub const MAX_RX_BUFFER_SIZE: usize = 16 * 1024;
+
+/// Source of receive buffers used to refill the ring.
+pub trait RxBufferProvider {
+    /// Returns a DMA buffer of at least `size` bytes, `None` if exhausted.
+    fn alloc(&mut self, size: usize) -> Option<DmaRegion>;
+
+    /// Like [`RxBufferProvider::alloc`], for a ring that requires its buffers
+    /// to start on an `align` byte boundary, see [`RxRing::with_alignment`].
+    /// Defaults to `alloc`; the ring hands misaligned buffers back through
+    /// [`RxBufferProvider::free`].
+    fn alloc_aligned(&mut self, size: usize, align: usize) -> Option<DmaRegion> {
+        let _ = align;
+        self.alloc(size)
+    }
+
+    /// Takes back a buffer from a freed ring. Defaults to
+    /// [`Kernel::dma_dealloc`].
+    fn free(&mut self, region: DmaRegion) {
+        kernel::dma_dealloc(region);
+    }
+}
+
+pub struct RxRing {
+    region: DmaRegion,
+    desc_count: usize,
+    buf_size: usize,
+    /// Alignment of the buffers' bus addresses, a power of two.
+    buf_align: usize,
+    buffers: Vec<Option<DmaRegion>>,
+    /// Buffers of consumed descriptors, re-posted before allocating new ones.
+    spare: Vec<DmaRegion>,
+    /// Next descriptor the hardware will complete.
+    next: usize,
+    /// Software copy of RDT, the first descriptor not owned by hardware.
+    tail: usize,
+    /// The descriptor at `next` was handed out as an [`RxFrame`] and is
+    /// returned to the hardware on the next poll.
+    held: bool,
+    /// The hardware writes descriptors back in the advanced layout.
+    advanced: bool,
+}
+
+/// A received frame, borrowing the ring's DMA buffer until the next
+/// [`crate::Igb::recv`].
+#[derive(Debug)]
+pub struct RxFrame<'a> {
+    pub data: &'a [u8],
+    /// Length from the descriptor, equal to `data.len()`.
+    pub length: usize,
+    /// Set for frames that failed the CRC check, see
+    /// [`RxDescriptor::crc_error`].
+    pub crc_error: bool,
+    /// Hardware classification, only available with advanced descriptors,
+    /// see [`RxRing::with_advanced_descriptors`].
+    pub packet_type: Option<PacketType>,
+}
+
+impl RxRing {
+    /// Builds a ring of `desc_count` descriptors in memory from
+    /// [`Kernel::dma_alloc`].
+    pub fn new(desc_count: usize) -> Result<Self, DError> {
+        if desc_count == 0 || !desc_count.is_multiple_of(8) {
+            return Err(DError::InvalidParameter);
+        }
+        let size = desc_count * size_of::<RxDescriptor>();
+        let region = kernel::dma_alloc(size, RING_ALIGN).ok_or(DError::NoMemory)?;
+        Self::with_memory(region, desc_count).inspect_err(|_| kernel::dma_dealloc(region))
+    }
+
+    /// Builds a ring over caller-provided memory, e.g. a pinned region from a
+    /// dedicated pool. The descriptors are cleared.
+    pub fn with_memory(region: DmaRegion, desc_count: usize) -> Result<Self, DError> {
+        if desc_count == 0 || !desc_count.is_multiple_of(8) {
+            return Err(DError::InvalidParameter);
+        }
+        region.check(desc_count * size_of::<RxDescriptor>(), RING_ALIGN)?;
+
+        let mut ring = Self {
+            region,
+            desc_count,
+            buf_size: DEFAULT_RX_BUFFER_SIZE,
+            buf_align: 1,
+            buffers: alloc::vec![None; desc_count],
+            spare: Vec::new(),
+            next: 0,
+            tail: 0,
+            held: false,
+            advanced: false,
+        };
+        ring.descriptors_mut().fill(RxDescriptor::default());
+        Ok(ring)
+    }
+
+    /// Sets the size of the receive buffers. Legacy rings take 256, 512,
+    /// 1024 or 2048 bytes (RCTL.BSIZE), rings with advanced descriptors any
+    /// multiple of 1024 up to [`MAX_RX_BUFFER_SIZE`] (SRRCTL.BSIZEPACKET). The
+    /// size is checked against the descriptor type when the ring is attached.
+    pub fn with_buffer_size(mut self, size: usize) -> Result<Self, DError> {
+        if size == 0 || size > MAX_RX_BUFFER_SIZE {
+            return Err(DError::InvalidParameter);
+        }
+        self.buf_size = size;
+        Ok(self)
+    }
+
+    pub fn buffer_size(&self) -> usize {
+        self.buf_size
+    }
+
+    /// Requires receive buffers to start on an `align` byte boundary, e.g. a
+    /// cache line or page, so the device never writes partial cache lines.
+    /// `align` must be a power of two.
+    pub fn with_alignment(mut self, align: usize) -> Result<Self, DError> {
+        if !align.is_power_of_two() {
+            return Err(DError::InvalidParameter);
+        }
+        self.buf_align = align;
+        Ok(self)
+    }
+
+    pub fn buffer_alignment(&self) -> usize {
+        self.buf_align
+    }
+
+    /// Has the queue use advanced one-buffer descriptors (SRRCTL.DESCTYPE),
+    /// whose write-back carries the packet type reported in
+    /// [`RxFrame::packet_type`]. Takes effect when the ring is attached.
+    pub fn with_advanced_descriptors(mut self) -> Self {
+        self.advanced = true;
+        self
+    }
+
+    pub fn uses_advanced_descriptors(&self) -> bool {
+        self.advanced
+    }
+
+    /// Number of descriptors software may hand to the hardware. One slot is
+    /// always kept back so a full ring is distinguishable from an empty one.
+    pub fn unused(&self) -> usize {
+        (self.next + self.desc_count - self.tail - 1) % self.desc_count
+    }
+
+    /// Hands every unused descriptor to the hardware, taking buffers from
+    /// `provider` once the ring has no spare ones left. Buffers outside `mask`
+    /// go straight back to `provider`. Returns the new tail to write into
+    /// RDT, or `None` if nothing was refilled.
+    pub fn refill(&mut self, provider: &mut dyn RxBufferProvider, mask: DmaMask) -> Option<u32> {
+        let align = self.buf_align;
+        self.post(|size| {
+            let buf = provider.alloc_aligned(size, align)?;
+            if buf.check(size, align).is_err() || !mask.contains(&buf) {
+                provider.free(buf);
+                return None;
+            }
+            Some(buf)
+        })
+    }
+
+    /// Like [`RxRing::refill`], but only re-posts buffers the ring already
+    /// owns.
+    pub fn recycle(&mut self) -> Option<u32> {
+        self.post(|_| None)
+    }
+
+    fn post(&mut self, mut alloc: impl FnMut(usize) -> Option<DmaRegion>) -> Option<u32> {
+        let mut refilled = false;
+        for _ in 0..self.unused() {
+            let idx = self.tail;
+            let bus = match self.buffers[idx] {
+                Some(buf) => buf.bus,
+                None => match self.spare.pop().or_else(|| alloc(self.buf_size)) {
+                    Some(buf) => {
+                        self.buffers[idx] = Some(buf);
+                        buf.bus
+                    }
+                    None => break,
+                },
+            };
+            self.write_desc(
+                idx,
+                RxDescriptor {
+                    addr: bus,
+                    ..Default::default()
+                },
+            );
+            self.tail = (idx + 1) % self.desc_count;
+            refilled = true;
+        }
+        refilled.then_some(self.tail as u32)
+    }
+
+    /// Gives the descriptor of the last polled frame back to software, so
+    /// the next refill re-posts it. Returns whether one was held.
+    pub(crate) fn release(&mut self) -> bool {
+        if !self.held {
+            return false;
+        }
+        self.held = false;
+        if let Some(buf) = self.buffers[self.next].take() {
+            self.spare.push(buf);
+        }
+        self.next = (self.next + 1) % self.desc_count;
+        true
+    }
+
+    /// Returns the next frame the hardware completed, if any. Its descriptor
+    /// is held until [`RxRing::release`].
+    pub(crate) fn poll(&mut self) -> Option<RxFrame<'_>> {
+        if self.held || self.next == self.tail {
+            return None;
+        }
+        let idx = self.next;
+        let (done, length, crc_error, packet_type) = if self.advanced {
+            let desc = unsafe {
+                self.desc_ptr()
+                    .cast::<AdvRxWriteback>()
+                    .add(idx)
+                    .read_volatile()
+            };
+            let done = desc.status().contains(RxStatus::DD);
+            (
+                done,
+                desc.length,
+                desc.crc_error(),
+                Some(desc.packet_type()),
+            )
+        } else {
+            let desc = unsafe { self.desc_ptr().add(idx).read_volatile() };
+            let done = desc.status().contains(RxStatus::DD);
+            (done, desc.length, desc.crc_error(), None)
+        };
+        if !done {
+            return None;
+        }
+        // Read the buffer only after seeing DD.
+        mb();
+
+        let buf = self.buffers[idx]?;
+        let length = (length as usize).min(buf.size);
+        self.held = true;
+        Some(RxFrame {
+            data: unsafe { core::slice::from_raw_parts(buf.virt.as_ptr(), length) },
+            length,
+            crc_error,
+            packet_type,
+        })
+    }
+
+    pub fn desc_count(&self) -> usize {
+        self.desc_count
+    }
+
+    /// Bus address of the first descriptor.
+    pub fn bus_addr(&self) -> u64 {
+        self.region.bus
+    }
+
+    pub(crate) fn region(&self) -> &DmaRegion {
+        &self.region
+    }
+
+    /// Length of the ring in bytes, as programmed into RDLEN.
+    pub fn byte_len(&self) -> usize {
+        self.desc_count * size_of::<RxDescriptor>()
+    }
+
+    pub fn descriptors(&self) -> &[RxDescriptor] {
+        unsafe { core::slice::from_raw_parts(self.desc_ptr().as_ptr(), self.desc_count) }
+    }
+
+    pub fn descriptors_mut(&mut self) -> &mut [RxDescriptor] {
+        unsafe { core::slice::from_raw_parts_mut(self.desc_ptr().as_ptr(), self.desc_count) }
+    }
+
+    /// Replaces every buffer with a fresh one from `provider` after a queue
+    /// stall left the ring state suspect. Receive `queue` is stopped, all
+    /// old buffers go back to `provider`, head and tail restart at 0 and the
+    /// queue is enabled again with a full ring, refilled under `mask` as in
+    /// [`RxRing::refill`].
+    pub fn replenish_all(
+        &mut self,
+        mac: &mut Mac,
+        queue: usize,
+        provider: &mut dyn RxBufferProvider,
+        mask: DmaMask,
+    ) -> Result<(), DError> {
+        mac.stop_rx_queue(queue)?;
+        for buf in self.buffers.iter_mut().filter_map(Option::take) {
+            provider.free(buf);
+        }
+        for buf in self.spare.drain(..) {
+            provider.free(buf);
+        }
+        self.descriptors_mut().fill(RxDescriptor::default());
+        self.next = 0;
+        self.tail = 0;
+        self.held = false;
+
+        mac.set_rx_ring(queue, self)?;
+        mac.start_rx_queue(queue)?;
+        if let Some(tail) = self.refill(provider, mask) {
+            mac.set_rx_tail(queue, tail);
+        }
+        Ok(())
+    }
+
+    /// Stops receive `queue` using this ring, hands the posted buffers back to
+    /// `provider` and returns the descriptor memory through
+    /// [`Kernel::dma_dealloc`]. If the queue does not stop, all memory is
+    /// leaked rather than freed under the device.
+    pub fn free(
+        self,
+        mac: &mut Mac,
+        queue: usize,
+        provider: &mut dyn RxBufferProvider,
+    ) -> Result<(), DError> {
+        mac.stop_rx_queue(queue)?;
+        for buf in self.buffers.into_iter().flatten().chain(self.spare) {
+            provider.free(buf);
+        }
+        kernel::dma_dealloc(self.region);
+        Ok(())
+    }
+
+    fn desc_ptr(&self) -> NonNull<RxDescriptor> {
+        self.region.virt.cast()
+    }
+
+    fn write_desc(&mut self, idx: usize, desc: RxDescriptor) {
+        unsafe { self.desc_ptr().add(idx).write_volatile(desc) }
+    }
+}
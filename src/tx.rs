@@ -0,0 +1,267 @@
+use core::{mem::size_of, ptr::NonNull};
+
+use log::error;
+
+use crate::{mac::Mac, osal::*, rx::RING_ALIGN};
+
+/// Legacy transmit descriptor.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TxDescriptor {
+    pub addr: u64,
+    pub length: u16,
+    pub cso: u8,
+    pub cmd: u8,
+    pub status: u8,
+    pub css: u8,
+    pub special: u16,
+}
+
+bitflags::bitflags! {
+    /// Legacy transmit descriptor command bits.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TxCommand: u8 {
+        /// End of packet.
+        const EOP = 1 << 0;
+        /// Insert FCS.
+        const IFCS = 1 << 1;
+        /// Report status, the hardware sets DD once the descriptor is done.
+        const RS = 1 << 3;
+    }
+
+    /// Legacy transmit descriptor status bits.
+    #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+    pub struct TxStatus: u8 {
+        /// Descriptor done.
+        const DD = 1 << 0;
+    }
+}
+
+impl TxDescriptor {
+    pub fn command(&self) -> TxCommand {
+        TxCommand::from_bits_retain(self.cmd)
+    }
+
+    pub fn status(&self) -> TxStatus {
+        TxStatus::from_bits_retain(self.status)
+    }
+}
+
+pub struct TxRing {
+    region: DmaRegion,
+    desc_count: usize,
+    /// Bounce buffer frames are copied into before transmission.
+    buffer: Option<DmaRegion>,
+    /// Software copy of TDT, the next descriptor to fill.
+    tail: usize,
+    /// Oldest submitted descriptor not yet reclaimed.
+    clean: usize,
+}
+
+impl TxRing {
+    /// Builds a ring of `desc_count` descriptors in memory from
+    /// [`Kernel::dma_alloc`].
+    pub fn new(desc_count: usize) -> Result<Self, DError> {
+        if desc_count == 0 || !desc_count.is_multiple_of(8) {
+            return Err(DError::InvalidParameter);
+        }
+        let size = desc_count * size_of::<TxDescriptor>();
+        let region = kernel::dma_alloc(size, RING_ALIGN).ok_or(DError::NoMemory)?;
+        Self::with_memory(region, desc_count).inspect_err(|_| kernel::dma_dealloc(region))
+    }
+
+    /// Builds a ring over caller-provided memory, e.g. a pinned region from a
+    /// dedicated pool. The descriptors are cleared.
+    pub fn with_memory(region: DmaRegion, desc_count: usize) -> Result<Self, DError> {
+        if desc_count == 0 || !desc_count.is_multiple_of(8) {
+            return Err(DError::InvalidParameter);
+        }
+        region.check(desc_count * size_of::<TxDescriptor>(), RING_ALIGN)?;
+
+        let mut ring = Self {
+            region,
+            desc_count,
+            buffer: None,
+            tail: 0,
+            clean: 0,
+        };
+        ring.descriptors_mut().fill(TxDescriptor::default());
+        Ok(ring)
+    }
+
+    /// Attaches the DMA buffer [`crate::Igb::send`] copies each frame into,
+    /// its size caps the frame length.
+    pub fn with_buffer(mut self, buffer: DmaRegion) -> Self {
+        self.buffer = Some(buffer);
+        self
+    }
+
+    /// Largest frame the attached buffer holds, 0 without a buffer.
+    pub fn buffer_size(&self) -> usize {
+        self.buffer.map_or(0, |buf| buf.size)
+    }
+
+    pub fn desc_count(&self) -> usize {
+        self.desc_count
+    }
+
+    pub(crate) fn region(&self) -> &DmaRegion {
+        &self.region
+    }
+
+    pub(crate) fn buffer(&self) -> Option<&DmaRegion> {
+        self.buffer.as_ref()
+    }
+
+    /// Copies `frame` into the buffer and fills the descriptor at the tail
+    /// for it. Returns the index of the descriptor and the new tail to write
+    /// into TDT.
+    ///
+    /// The single buffer is only reused once every earlier descriptor has
+    /// been written back, until then this fails with [`DError::Busy`].
+    pub(crate) fn push(&mut self, frame: &[u8]) -> Result<(usize, u32), DError> {
+        let buf = self.buffer.ok_or(DError::InvalidParameter)?;
+        if frame.is_empty() || frame.len() > buf.size {
+            return Err(DError::InvalidParameter);
+        }
+        self.reclaim();
+        if self.pending() > 0 {
+            return Err(DError::Busy);
+        }
+        unsafe {
+            core::ptr::copy_nonoverlapping(frame.as_ptr(), buf.virt.as_ptr(), frame.len());
+        }
+
+        let idx = self.tail;
+        let desc = TxDescriptor {
+            addr: buf.bus,
+            length: frame.len() as u16,
+            cmd: (TxCommand::EOP | TxCommand::IFCS | TxCommand::RS).bits(),
+            ..Default::default()
+        };
+        unsafe { self.desc_ptr().add(idx).write_volatile(desc) };
+        self.tail = (idx + 1) % self.desc_count;
+        Ok((idx, self.tail as u32))
+    }
+
+    /// Whether the hardware wrote back the descriptor at `idx`.
+    pub(crate) fn is_done(&self, idx: usize) -> bool {
+        let desc = unsafe { self.desc_ptr().add(idx).read_volatile() };
+        desc.status().contains(TxStatus::DD)
+    }
+
+    /// Number of submitted descriptors that have not been reclaimed yet, i.e.
+    /// whose buffers may still be owned by the hardware.
+    pub fn pending(&self) -> usize {
+        (self.tail + self.desc_count - self.clean) % self.desc_count
+    }
+
+    /// Reclaims the descriptors the hardware wrote back, in submission order,
+    /// and returns how many were reclaimed.
+    pub fn reclaim(&mut self) -> usize {
+        let mut count = 0;
+        while self.clean != self.tail && self.is_done(self.clean) {
+            self.clean = (self.clean + 1) % self.desc_count;
+            count += 1;
+        }
+        count
+    }
+
+    /// Forgets all descriptors after the queue was rewound to an empty ring.
+    pub(crate) fn rewind(&mut self) {
+        self.tail = 0;
+        self.clean = 0;
+        self.descriptors_mut().fill(TxDescriptor::default());
+    }
+
+    /// Bus address of the first descriptor.
+    pub fn bus_addr(&self) -> u64 {
+        self.region.bus
+    }
+
+    /// Length of the ring in bytes, as programmed into TDLEN.
+    pub fn byte_len(&self) -> usize {
+        self.desc_count * size_of::<TxDescriptor>()
+    }
+
+    pub fn descriptors(&self) -> &[TxDescriptor] {
+        unsafe { core::slice::from_raw_parts(self.desc_ptr().as_ptr(), self.desc_count) }
+    }
+
+    pub fn descriptors_mut(&mut self) -> &mut [TxDescriptor] {
+        unsafe { core::slice::from_raw_parts_mut(self.desc_ptr().as_ptr(), self.desc_count) }
+    }
+
+    /// Checks that transmit queue 0 reads back this ring's base and length,
+    /// catching writes that did not reach the device (wrong BAR mapping,
+    /// memory decoding disabled).
+    pub fn verify(&self, mac: &Mac) -> Result<(), DError> {
+        let base = mac.tx_ring_base(0)?;
+        let len = mac.tx_ring_len(0)?;
+        if base != self.bus_addr() || len as usize != self.byte_len() {
+            error!(
+                "TX ring readback mismatch: base {:#x} len {:#x}, expected {:#x} len {:#x}",
+                base,
+                len,
+                self.bus_addr(),
+                self.byte_len()
+            );
+            return Err(DError::Unknown("TX ring readback mismatch"));
+        }
+        Ok(())
+    }
+
+    /// Returns whether the hardware wrote back the descriptor before `tail`,
+    /// i.e. the last one submitted. An unused ring counts as written back.
+    pub fn last_written_back(&self, tail: usize) -> bool {
+        let idx = (tail + self.desc_count - 1) % self.desc_count;
+        let desc = unsafe { self.desc_ptr().add(idx).read_volatile() };
+        !desc.command().contains(TxCommand::RS) || desc.status().contains(TxStatus::DD)
+    }
+
+    /// Stops transmit `queue` using this ring and returns the descriptor
+    /// memory and buffer through [`Kernel::dma_dealloc`]. If the queue does not stop,
+    /// the memory is leaked rather than freed under the device.
+    pub fn free(self, mac: &mut Mac, queue: usize) -> Result<(), DError> {
+        mac.stop_tx_queue(queue)?;
+        if let Some(buf) = self.buffer {
+            kernel::dma_dealloc(buf);
+        }
+        kernel::dma_dealloc(self.region);
+        Ok(())
+    }
+
+    fn desc_ptr(&self) -> NonNull<TxDescriptor> {
+        self.region.virt.cast()
+    }
+}
+
+/// Consecutive watchdog checks without head progress before the queue is
+/// considered hung.
+pub const TX_HANG_CHECKS: u32 = 3;
+
+/// Tracks transmit head progress across periodic checks.
+#[derive(Debug, Default)]
+pub(crate) struct TxWatchdog {
+    last_head: u32,
+    stalled_checks: u32,
+}
+
+impl TxWatchdog {
+    /// Feeds one observation of the hardware head/tail, returns `true` once the
+    /// head has been stuck with work pending for [`TX_HANG_CHECKS`] checks.
+    pub fn check(&mut self, head: u32, tail: u32) -> bool {
+        if head == tail || head != self.last_head {
+            self.last_head = head;
+            self.stalled_checks = 0;
+            return false;
+        }
+
+        self.stalled_checks += 1;
+        self.stalled_checks >= TX_HANG_CHECKS
+    }
+
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
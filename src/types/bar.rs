@@ -1,9 +1,12 @@
 use core::{fmt::Debug, ops::Index};
 
 use alloc::vec::Vec;
-use pci_types::{Bar, BarWriteError, EndpointHeader, HeaderType, PciAddress, PciHeader};
+use pci_types::{
+    Bar, BarWriteError, CommandRegister, ConfigRegionAccess, EndpointHeader, HeaderType,
+    PciAddress, PciHeader,
+};
 
-use crate::{Chip, RootComplex};
+use crate::{Chip, DError, Endpoint, RootComplex};
 
 #[derive(Clone)]
 pub enum BarVec {
@@ -49,7 +52,20 @@ pub(crate) trait BarHeader: Sized {
     fn header_type(&self) -> HeaderType;
 
     fn parse_bar<C: Chip>(&self, slot_size: usize, access: &RootComplex<C>) -> BarVec {
-        let bar0 = match self.read_bar(0, access) {
+        self.collect_bars(slot_size, |slot| self.read_bar(slot, access))
+    }
+
+    /// Like [`BarHeader::parse_bar`], but only reads the BAR registers. No
+    /// all-ones sizing write is done, so decoding stays untouched and the
+    /// sizes are reported as 0.
+    fn discover_bar<C: Chip>(&self, slot_size: usize, access: &RootComplex<C>) -> BarVec {
+        self.collect_bars(slot_size, |slot| {
+            read_bar_unsized(self.address(), slot, access)
+        })
+    }
+
+    fn collect_bars(&self, slot_size: usize, read: impl Fn(usize) -> Option<Bar>) -> BarVec {
+        let bar0 = match read(0) {
             Some(bar0) => bar0,
             None => {
                 return BarVec::Memory32(BarVecT {
@@ -78,7 +94,7 @@ pub(crate) trait BarHeader: Sized {
                         address,
                         size,
                         prefetchable,
-                    }) = self.read_bar(i, access)
+                    }) = read(i)
                     {
                         v[i] = Some(Bar32 {
                             address,
@@ -111,7 +127,7 @@ pub(crate) trait BarHeader: Sized {
                         address,
                         size,
                         prefetchable,
-                    }) = self.read_bar(i * 2, access)
+                    }) = read(i * 2)
                     {
                         v[i] = Some(Bar64 {
                             address,
@@ -132,7 +148,7 @@ pub(crate) trait BarHeader: Sized {
                 v[0] = Some(BarIO { port });
 
                 (1..slot_size).for_each(|i| {
-                    if let Some(Bar::Io { port }) = self.read_bar(i, access) {
+                    if let Some(Bar::Io { port }) = read(i) {
                         v[i] = Some(BarIO { port });
                     }
                 });
@@ -147,6 +163,39 @@ pub(crate) trait BarHeader: Sized {
     }
 }
 
+/// Decodes BAR `slot` from its register value alone.
+fn read_bar_unsized<C: Chip>(
+    address: PciAddress,
+    slot: usize,
+    access: &RootComplex<C>,
+) -> Option<Bar> {
+    let offset = 0x10 + slot as u16 * 4;
+    let raw = access.read_config(address, offset);
+    if raw == 0 {
+        return None;
+    }
+    if raw & 0b1 != 0 {
+        return Some(Bar::Io { port: raw & !0b11 });
+    }
+    let prefetchable = raw & 0b1000 != 0;
+    match (raw >> 1) & 0b11 {
+        0b00 => Some(Bar::Memory32 {
+            address: raw & !0xf,
+            size: 0,
+            prefetchable,
+        }),
+        0b10 if slot < 5 => {
+            let high = access.read_config(address, offset + 4) as u64;
+            Some(Bar::Memory64 {
+                address: high << 32 | (raw & !0xf) as u64,
+                size: 0,
+                prefetchable,
+            })
+        }
+        _ => None,
+    }
+}
+
 impl Debug for Bar32 {
     fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
         write!(
@@ -229,6 +278,32 @@ impl BarVecT<Bar64> {
     }
 }
 
+impl BarVecT<BarIO> {
+    /// Sizes I/O BAR `index` by writing all ones, restoring it afterwards.
+    /// I/O decoding must be off.
+    pub(crate) fn size<C: Chip>(&self, index: usize, access: &RootComplex<C>) -> u32 {
+        let offset = 0x10 + index as u16 * 4;
+        unsafe {
+            let orig = access.read(self.address, offset);
+            access.write(self.address, offset, u32::MAX);
+            let mask = access.read(self.address, offset) & !0b11;
+            access.write(self.address, offset, orig);
+            // 16-bit decoders hardwire the upper half to 0.
+            let mask = if mask >> 16 == 0 {
+                mask | 0xffff_0000
+            } else {
+                mask
+            };
+            (!mask).wrapping_add(1)
+        }
+    }
+
+    pub(crate) fn set<C: Chip>(&self, index: usize, port: u32, access: &RootComplex<C>) {
+        let offset = 0x10 + index as u16 * 4;
+        unsafe { access.write(self.address, offset, port) };
+    }
+}
+
 impl<T> Index<usize> for BarVecT<T> {
     type Output = Option<T>;
 
@@ -242,3 +317,66 @@ impl<T> BarVecT<T> {
         self.data.iter()
     }
 }
+
+impl Endpoint {
+    /// Moves memory BAR `index` to `new_addr`. Memory decode is turned off
+    /// while the (possibly 64-bit) BAR is rewritten and restored afterwards.
+    /// For 64-bit BARs `index` counts BAR pairs, as in [`BarVec`].
+    ///
+    /// `new_addr` must be aligned to the BAR size, and fit in 32 bits for
+    /// 32-bit BARs. I/O BARs cannot be remapped.
+    pub fn remap_bar<C: Chip>(
+        &mut self,
+        root: &mut RootComplex<C>,
+        index: usize,
+        new_addr: u64,
+    ) -> Result<(), DError> {
+        let size = match &self.bar {
+            BarVec::Memory32(v) => {
+                let bar = v.data.get(index).and_then(Option::as_ref);
+                let bar = bar.ok_or(DError::InvalidParameter)?;
+                if new_addr > u32::MAX as u64 {
+                    return Err(DError::InvalidParameter);
+                }
+                bar.size as u64
+            }
+            BarVec::Memory64(v) => {
+                let bar = v.data.get(index).and_then(Option::as_ref);
+                bar.ok_or(DError::InvalidParameter)?.size
+            }
+            BarVec::Io(_) => return Err(DError::InvalidParameter),
+        };
+        if size == 0 || !new_addr.is_multiple_of(size) {
+            return Err(DError::InvalidParameter);
+        }
+
+        let mut decode = false;
+        self.update_command(root, |mut cmd| {
+            decode = cmd.contains(CommandRegister::MEMORY_ENABLE);
+            cmd.remove(CommandRegister::MEMORY_ENABLE);
+            cmd
+        });
+
+        let res = match &mut self.bar {
+            BarVec::Memory32(v) => v.set(index, new_addr as u32, root).map(|_| {
+                if let Some(bar) = &mut v.data[index] {
+                    bar.address = new_addr as u32;
+                }
+            }),
+            BarVec::Memory64(v) => v.set(index, new_addr, root).map(|_| {
+                if let Some(bar) = &mut v.data[index] {
+                    bar.address = new_addr;
+                }
+            }),
+            BarVec::Io(_) => unreachable!(),
+        };
+
+        if decode {
+            self.update_command(root, |mut cmd| {
+                cmd.insert(CommandRegister::MEMORY_ENABLE);
+                cmd
+            });
+        }
+        res.map_err(|_| DError::InvalidParameter)
+    }
+}
@@ -0,0 +1,169 @@
+use alloc::vec::Vec;
+use bit_field::BitField;
+use pci_types::{capability::PciCapability, PciAddress};
+
+use crate::{Chip, Endpoint, RootComplex};
+
+/// Config space offset of the capabilities pointer.
+const CAP_PTR: u16 = 0x34;
+/// Capabilities List bit in the status half of the command/status register.
+const STATUS_CAP_LIST: usize = 16 + 4;
+/// Legacy capabilities live in the 192 bytes after the standard header.
+const CAP_START: u16 = 0x40;
+
+/// Power Management capability ID.
+pub const CAP_ID_PM: u8 = 0x01;
+/// MSI capability ID.
+pub const CAP_ID_MSI: u8 = 0x05;
+/// PCI Express capability ID.
+pub const CAP_ID_PCIE: u8 = 0x10;
+/// MSI-X capability ID.
+pub const CAP_ID_MSIX: u8 = 0x11;
+
+/// A capability in the legacy list, see [`Endpoint::capability_list`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Capability {
+    PowerManagement,
+    Msi,
+    PciExpress,
+    MsiX,
+    /// Any other capability, by ID.
+    Unknown(u8),
+}
+
+impl From<u8> for Capability {
+    fn from(id: u8) -> Self {
+        match id {
+            CAP_ID_PM => Self::PowerManagement,
+            CAP_ID_MSI => Self::Msi,
+            CAP_ID_PCIE => Self::PciExpress,
+            CAP_ID_MSIX => Self::MsiX,
+            id => Self::Unknown(id),
+        }
+    }
+}
+
+/// Extended capabilities start right after the legacy 256 byte config space.
+const EXT_CAP_START: u16 = 0x100;
+/// End of the 4K extended config space.
+const EXT_CAP_END: u16 = 0x1000;
+
+/// Advanced Error Reporting extended capability ID.
+pub const EXT_CAP_ID_AER: u16 = 0x0001;
+/// Device Serial Number extended capability ID.
+pub const EXT_CAP_ID_DSN: u16 = 0x0003;
+/// Single Root I/O Virtualization extended capability ID.
+pub const EXT_CAP_ID_SRIOV: u16 = 0x0010;
+
+/// Which of the commonly used capabilities a device has, see
+/// [`Endpoint::capability_summary`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CapabilitySummary {
+    pub has_msi: bool,
+    pub has_msix: bool,
+    pub has_pcie: bool,
+    pub has_pm: bool,
+    pub has_aer: bool,
+    pub has_sriov: bool,
+}
+
+impl Endpoint {
+    /// Walks the legacy capability list from the capabilities pointer at
+    /// 0x34, reading config space directly. Empty if the status register
+    /// reports no list.
+    pub fn capability_list<C: Chip>(&self, root: &RootComplex<C>) -> Vec<Capability> {
+        root.capability_offsets(self.address)
+            .into_iter()
+            .map(|(_, id)| Capability::from(id))
+            .collect()
+    }
+
+    /// Offset of the legacy capability `id` in config space.
+    pub fn find_capability<C: Chip>(&self, root: &RootComplex<C>, id: u8) -> Option<u16> {
+        root.capability_offsets(self.address)
+            .into_iter()
+            .find_map(|(offset, cap)| (cap == id).then_some(offset))
+    }
+
+    /// Offset of the extended capability `id` in config space, walking the
+    /// list that starts at 0x100. Only PCI Express devices have one.
+    pub fn find_ext_capability<C: Chip>(&self, root: &RootComplex<C>, id: u16) -> Option<u16> {
+        self.pcie_capability_offset()?;
+
+        let mut offset = EXT_CAP_START;
+        // Every header is at least 4 bytes, bounding the walk on a looped list.
+        for _ in 0..(EXT_CAP_END - EXT_CAP_START) / 4 {
+            let header = root.read_config(self.address, offset);
+            if header == 0 || header == u32::MAX {
+                return None;
+            }
+            if header.get_bits(0..16) as u16 == id {
+                return Some(offset);
+            }
+            offset = header.get_bits(20..32) as u16;
+            if offset < EXT_CAP_START {
+                return None;
+            }
+        }
+        None
+    }
+
+    /// The 64-bit device serial number, `None` without the Device Serial
+    /// Number capability.
+    pub fn serial_number<C: Chip>(&self, root: &RootComplex<C>) -> Option<u64> {
+        let cap = self.find_ext_capability(root, EXT_CAP_ID_DSN)?;
+        let low = root.read_config(self.address, cap + 0x04) as u64;
+        let high = root.read_config(self.address, cap + 0x08) as u64;
+        Some(high << 32 | low)
+    }
+
+    /// Number of MSI-X vectors the device implements, `None` without an MSI-X
+    /// capability. The read-only table size is captured at enumeration, so no
+    /// config access is needed.
+    pub fn msix_table_size(&self) -> Option<u16> {
+        self.capabilities.iter().find_map(|cap| match cap {
+            PciCapability::MsiX(msix) => Some(msix.table_size()),
+            _ => None,
+        })
+    }
+
+    /// Summarizes which of MSI, MSI-X, PCI Express, power management, AER and
+    /// SR-IOV the device supports.
+    pub fn capability_summary<C: Chip>(&self, root: &RootComplex<C>) -> CapabilitySummary {
+        let mut summary = CapabilitySummary::default();
+        for cap in &self.capabilities {
+            match cap {
+                PciCapability::Msi(_) => summary.has_msi = true,
+                PciCapability::MsiX(_) => summary.has_msix = true,
+                PciCapability::PciExpress(_) => summary.has_pcie = true,
+                PciCapability::PowerManagement(_) => summary.has_pm = true,
+                _ => {}
+            }
+        }
+        summary.has_aer = self.find_ext_capability(root, EXT_CAP_ID_AER).is_some();
+        summary.has_sriov = self.find_ext_capability(root, EXT_CAP_ID_SRIOV).is_some();
+        summary
+    }
+}
+
+impl<C: Chip> RootComplex<C> {
+    /// Offsets and IDs of the legacy capabilities, in list order.
+    pub(crate) fn capability_offsets(&self, address: PciAddress) -> Vec<(u16, u8)> {
+        let mut caps = Vec::new();
+        if !self.read_config(address, 0x04).get_bit(STATUS_CAP_LIST) {
+            return caps;
+        }
+
+        let mut offset = self.read_config(address, CAP_PTR).get_bits(0..8) as u16 & !0x3;
+        // Every capability takes at least 4 bytes, bounding the walk on a looped list.
+        for _ in 0..(EXT_CAP_START - CAP_START) / 4 {
+            if offset < CAP_START {
+                break;
+            }
+            let header = self.read_config(address, offset);
+            caps.push((offset, header.get_bits(0..8) as u8));
+            offset = header.get_bits(8..16) as u16 & !0x3;
+        }
+        caps
+    }
+}
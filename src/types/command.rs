@@ -0,0 +1,60 @@
+use core::fmt::Display;
+
+use crate::CommandRegister;
+
+/// Typed accessors for the command register, which is re-exported from
+/// `pci_types` and so cannot carry inherent methods here.
+pub trait CommandRegisterExt {
+    fn io_enable(&self) -> bool;
+    fn memory_enable(&self) -> bool;
+    fn bus_master_enable(&self) -> bool;
+    fn serr_enable(&self) -> bool;
+    /// INTx assertion is disabled, as required while MSI/MSI-X is in use.
+    fn interrupt_disable(&self) -> bool;
+    /// Formats the set flags, e.g. `MEMORY_ENABLE | BUS_MASTER_ENABLE`.
+    fn display(&self) -> CommandFlags;
+}
+
+impl CommandRegisterExt for CommandRegister {
+    fn io_enable(&self) -> bool {
+        self.contains(CommandRegister::IO_ENABLE)
+    }
+
+    fn memory_enable(&self) -> bool {
+        self.contains(CommandRegister::MEMORY_ENABLE)
+    }
+
+    fn bus_master_enable(&self) -> bool {
+        self.contains(CommandRegister::BUS_MASTER_ENABLE)
+    }
+
+    fn serr_enable(&self) -> bool {
+        self.contains(CommandRegister::SERR_ENABLE)
+    }
+
+    fn interrupt_disable(&self) -> bool {
+        self.contains(CommandRegister::INTERRUPT_DISABLE)
+    }
+
+    fn display(&self) -> CommandFlags {
+        CommandFlags(*self)
+    }
+}
+
+/// [`Display`] adapter returned by [`CommandRegisterExt::display`].
+pub struct CommandFlags(CommandRegister);
+
+impl Display for CommandFlags {
+    fn fmt(&self, f: &mut core::fmt::Formatter<'_>) -> core::fmt::Result {
+        if self.0.is_empty() {
+            return write!(f, "(none)");
+        }
+        for (i, (name, _)) in self.0.iter_names().enumerate() {
+            if i > 0 {
+                write!(f, " | ")?;
+            }
+            write!(f, "{}", name)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,373 @@
+use bit_field::BitField;
+use pci_types::{capability::PciCapability, PciAddress};
+
+use core::time::Duration;
+
+use crate::{osal::*, Chip, Endpoint, PciPciBridge, RootComplex, CAP_ID_PCIE};
+
+/// Device Capabilities register, relative to the PCI Express capability.
+const PCIE_DEVCAP: u16 = 0x04;
+/// Device Control (low half) and Device Status (high half).
+const PCIE_DEVCTL: u16 = 0x08;
+/// Link Control (low half) and Link Status (high half).
+const PCIE_LNKCTL: u16 = 0x10;
+
+/// Function Level Reset Capability bit in Device Capabilities.
+const DEVCAP_FLR: usize = 28;
+/// Initiate Function Level Reset bit in Device Control.
+const DEVCTL_INITIATE_FLR: usize = 15;
+/// Transactions Pending bit in Device Status.
+const DEVSTA_TRANSACTIONS_PENDING: usize = 16 + 5;
+/// PCI Express Capabilities register, device/port type in bits 4..8.
+const PCIE_CAPS: u16 = 0x00;
+/// Device/port types whose Link Control has a working Retrain Link bit.
+const PORT_TYPE_ROOT: u32 = 0x4;
+const PORT_TYPE_DOWNSTREAM: u32 = 0x6;
+
+/// Retrain Link bit in Link Control.
+const LNKCTL_RETRAIN: usize = 5;
+/// Link Training bit in Link Status.
+const LNKSTA_TRAINING: usize = 16 + 11;
+/// Link Bandwidth Management Status bit in Link Status, set once a retrain
+/// requested by software completed. Write-1-to-clear.
+const LNKSTA_LBMS: usize = 16 + 14;
+/// Polling of Link Training, a retrain normally finishes in a few
+/// milliseconds, give up after ~1s.
+const LINK_TRAINING_INTERVAL: Duration = Duration::from_millis(1);
+const LINK_TRAINING_TRIES: usize = 1000;
+
+/// Time a function may take to complete an FLR before it has to respond to
+/// config requests again.
+const FLR_DELAY: Duration = Duration::from_millis(100);
+
+/// Largest read request the PCI Express spec allows.
+const MAX_READ_REQUEST: u16 = 4096;
+
+/// Max Payload Size and Max Read Request Size, in bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DmaTuning {
+    /// Max Payload Size programmed in Device Control.
+    pub max_payload: u16,
+    /// Max Payload Size the device supports.
+    pub max_payload_supported: u16,
+    /// Max Read Request Size programmed in Device Control.
+    pub max_read_request: u16,
+    /// Upper bound for Max Read Request Size.
+    pub max_read_request_supported: u16,
+}
+
+impl DmaTuning {
+    fn decode(devcap: u32, devctl: u32) -> Self {
+        Self {
+            max_payload: decode_size(devctl.get_bits(5..8)),
+            max_payload_supported: decode_size(devcap.get_bits(0..3)),
+            max_read_request: decode_size(devctl.get_bits(12..15)),
+            max_read_request_supported: MAX_READ_REQUEST,
+        }
+    }
+}
+
+/// Allowed Max Read Request Size values.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+#[repr(u8)]
+pub enum MrrsSize {
+    B128 = 0,
+    B256 = 1,
+    B512 = 2,
+    B1024 = 3,
+    B2048 = 4,
+    B4096 = 5,
+}
+
+impl MrrsSize {
+    pub fn bytes(&self) -> u16 {
+        decode_size(*self as u32)
+    }
+}
+
+/// Negotiated link speed from the Link Status register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum PcieLinkSpeed {
+    /// 2.5 GT/s
+    Gen1,
+    /// 5 GT/s
+    Gen2,
+    /// 8 GT/s
+    Gen3,
+}
+
+impl PcieLinkSpeed {
+    fn decode(lnksta: u32) -> Option<Self> {
+        match lnksta.get_bits(0..4) {
+            1 => Some(Self::Gen1),
+            2 => Some(Self::Gen2),
+            3 => Some(Self::Gen3),
+            _ => None,
+        }
+    }
+
+    /// Transfer rate in megatransfers per second.
+    pub fn mega_transfers(&self) -> u32 {
+        match self {
+            Self::Gen1 => 2500,
+            Self::Gen2 => 5000,
+            Self::Gen3 => 8000,
+        }
+    }
+}
+
+/// Generation of a PCI Express link, named by its transfer rate.
+pub type PcieGen = PcieLinkSpeed;
+
+/// Negotiated speed and width from the Link Status register.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LinkStatus {
+    pub speed: PcieGen,
+    /// Lane count, 1 for x1, 4 for x4 and so on.
+    pub width: u8,
+}
+
+impl LinkStatus {
+    fn decode(lnksta: u32) -> Option<Self> {
+        Some(Self {
+            speed: PcieLinkSpeed::decode(lnksta)?,
+            width: lnksta.get_bits(4..10) as u8,
+        })
+    }
+}
+
+/// Sizes in the device registers are encoded as `128 << n`.
+fn decode_size(encoded: u32) -> u16 {
+    128 << encoded.min(5)
+}
+
+impl Endpoint {
+    /// Offset of the PCI Express capability in config space.
+    pub fn pcie_capability_offset(&self) -> Option<u16> {
+        self.capabilities.iter().find_map(|cap| match cap {
+            PciCapability::PciExpress(addr) => Some(addr.offset),
+            _ => None,
+        })
+    }
+
+    /// Reports the programmed payload and read request sizes next to what the
+    /// device supports, to spot links firmware left at the 128 byte minimum.
+    ///
+    /// Returns `None` if the device has no PCI Express capability.
+    pub fn dma_tuning<C: Chip>(&self, root: &RootComplex<C>) -> Option<DmaTuning> {
+        let cap = self.pcie_capability_offset()?;
+        let devcap = root.read_config(self.address, cap + PCIE_DEVCAP);
+        let devctl = root.read_config(self.address, cap + PCIE_DEVCTL);
+        Some(DmaTuning::decode(devcap, devctl))
+    }
+
+    /// Programs the Max Read Request Size field of Device Control. Devices
+    /// advertise no upper bound, every [`MrrsSize`] is valid; fails with
+    /// [`DError::InvalidParameter`] without a PCI Express capability.
+    pub fn set_max_read_request<C: Chip>(
+        &self,
+        root: &mut RootComplex<C>,
+        size: MrrsSize,
+    ) -> Result<(), DError> {
+        let cap = self
+            .pcie_capability_offset()
+            .ok_or(DError::InvalidParameter)?;
+        // The upper half is Device Status, whose bits are write-1-to-clear.
+        let mut devctl = root.read_config(self.address, cap + PCIE_DEVCTL) & 0xffff;
+        devctl.set_bits(12..15, size as u32);
+        root.write_config(self.address, cap + PCIE_DEVCTL, devctl);
+        Ok(())
+    }
+
+    /// Current link speed, `None` without a PCI Express capability or for a
+    /// speed encoding newer than Gen3.
+    pub fn link_speed<C: Chip>(&self, root: &RootComplex<C>) -> Option<PcieLinkSpeed> {
+        let cap = self.pcie_capability_offset()?;
+        let lnksta = root.read_config(self.address, cap + PCIE_LNKCTL) >> 16;
+        PcieLinkSpeed::decode(lnksta)
+    }
+
+    /// Resets only this function through Initiate Function Level Reset in
+    /// Device Control, then waits the 100ms the spec grants for recovery.
+    ///
+    /// Fails with [`DError::InvalidParameter`] if the device does not
+    /// advertise FLR. The reset clears the command register and BARs, so the
+    /// caller has to restore them before using the device again.
+    pub fn function_level_reset<C: Chip>(&self, root: &mut RootComplex<C>) -> Result<(), DError> {
+        let cap = self
+            .pcie_capability_offset()
+            .ok_or(DError::InvalidParameter)?;
+        if !root
+            .read_config(self.address, cap + PCIE_DEVCAP)
+            .get_bit(DEVCAP_FLR)
+        {
+            return Err(DError::InvalidParameter);
+        }
+
+        // Give outstanding requests the same time to finish, they are lost
+        // on reset either way.
+        let _ = wait_for(
+            "DEVSTA.TRANSACTIONS_PENDING",
+            || {
+                !root
+                    .read_config(self.address, cap + PCIE_DEVCTL)
+                    .get_bit(DEVSTA_TRANSACTIONS_PENDING)
+            },
+            Duration::from_millis(10),
+            Some(10),
+        );
+
+        // The upper half is Device Status, whose bits are write-1-to-clear.
+        let mut devctl = root.read_config(self.address, cap + PCIE_DEVCTL) & 0xffff;
+        devctl.set_bit(DEVCTL_INITIATE_FLR, true);
+        root.write_config(self.address, cap + PCIE_DEVCTL, devctl);
+        kernel::sleep(FLR_DELAY);
+
+        if root.read_config(self.address, 0) == u32::MAX {
+            return Err(DError::TimeoutAt("FLR completion"));
+        }
+        Ok(())
+    }
+}
+
+impl<C: Chip> RootComplex<C> {
+    /// Negotiated speed and width of the link to the function at `addr`. A
+    /// card running at x1 in an x4 slot shows up here.
+    ///
+    /// Returns `None` without a PCI Express capability or for a speed
+    /// encoding newer than Gen3.
+    pub fn link_status(&self, addr: PciAddress) -> Option<LinkStatus> {
+        let cap = self
+            .capability_offsets(addr)
+            .into_iter()
+            .find_map(|(offset, id)| (id == CAP_ID_PCIE).then_some(offset))?;
+        LinkStatus::decode(self.read_config(addr, cap + PCIE_LNKCTL) >> 16)
+    }
+}
+
+impl PciPciBridge {
+    /// Asks the link below this port to train again through Retrain Link in
+    /// Link Control and waits for Link Training in Link Status to clear.
+    /// Worth trying when [`RootComplex::link_status`] reports a narrower or
+    /// slower link than expected.
+    ///
+    /// Only root ports and switch downstream ports can retrain their link,
+    /// anything else fails with [`DError::InvalidParameter`].
+    pub fn retrain_link<C: Chip>(&self, root: &mut RootComplex<C>) -> Result<(), DError> {
+        let cap = root
+            .capability_offsets(self.address)
+            .into_iter()
+            .find_map(|(offset, id)| (id == CAP_ID_PCIE).then_some(offset))
+            .ok_or(DError::InvalidParameter)?;
+        let port_type = root
+            .read_config(self.address, cap + PCIE_CAPS)
+            .get_bits(20..24);
+        if !matches!(port_type, PORT_TYPE_ROOT | PORT_TYPE_DOWNSTREAM) {
+            return Err(DError::InvalidParameter);
+        }
+        let lnksta = |root: &RootComplex<C>| root.read_config(self.address, cap + PCIE_LNKCTL);
+
+        // A training still in progress would swallow the request.
+        wait_for(
+            "LNKSTA.LINK_TRAINING clear",
+            || !lnksta(root).get_bit(LNKSTA_TRAINING),
+            LINK_TRAINING_INTERVAL,
+            Some(LINK_TRAINING_TRIES),
+        )?;
+
+        // The upper half is Link Status, whose bits are write-1-to-clear.
+        // Clear a stale LBMS along with setting Retrain Link, so that it can
+        // report this retrain.
+        let mut lnkctl = lnksta(root) & 0xffff;
+        lnkctl.set_bit(LNKCTL_RETRAIN, true);
+        lnkctl.set_bit(LNKSTA_LBMS, true);
+        root.write_config(self.address, cap + PCIE_LNKCTL, lnkctl);
+
+        // A short retrain may be over before the first read, LBMS still
+        // records that it happened.
+        wait_for(
+            "LNKSTA.LINK_TRAINING set",
+            || {
+                let sta = lnksta(root);
+                sta.get_bit(LNKSTA_TRAINING) || sta.get_bit(LNKSTA_LBMS)
+            },
+            LINK_TRAINING_INTERVAL,
+            Some(LINK_TRAINING_TRIES),
+        )?;
+        wait_for(
+            "LNKSTA.LINK_TRAINING clear",
+            || !lnksta(root).get_bit(LNKSTA_TRAINING),
+            LINK_TRAINING_INTERVAL,
+            Some(LINK_TRAINING_TRIES),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decodes_programmed_and_supported_sizes() {
+        // DEVCAP: 512 byte MPS supported. DEVCTL: MPS 256, MRRS 2048.
+        let devcap = 0b010;
+        let devctl = (0b001 << 5) | (0b100 << 12);
+        assert_eq!(
+            DmaTuning::decode(devcap, devctl),
+            DmaTuning {
+                max_payload: 256,
+                max_payload_supported: 512,
+                max_read_request: 2048,
+                max_read_request_supported: MAX_READ_REQUEST,
+            }
+        );
+    }
+
+    #[test]
+    fn ignores_neighbouring_device_control_bits() {
+        // Relaxed ordering, no snoop and Device Status bits around the fields.
+        let devctl = (1 << 4) | (1 << 11) | (0b011 << 12) | (0xffff << 16);
+        let tuning = DmaTuning::decode(0, devctl);
+        assert_eq!(tuning.max_payload, 128);
+        assert_eq!(tuning.max_payload_supported, 128);
+        assert_eq!(tuning.max_read_request, 1024);
+    }
+
+    #[test]
+    fn clamps_reserved_size_encodings() {
+        assert_eq!(decode_size(5), 4096);
+        assert_eq!(decode_size(6), 4096);
+        assert_eq!(decode_size(7), 4096);
+
+        let tuning = DmaTuning::decode(0b111, (0b110 << 5) | (0b111 << 12));
+        assert_eq!(tuning.max_payload, 4096);
+        assert_eq!(tuning.max_payload_supported, 4096);
+        assert_eq!(tuning.max_read_request, 4096);
+    }
+
+    #[test]
+    fn decodes_link_speed() {
+        assert_eq!(PcieLinkSpeed::decode(1), Some(PcieLinkSpeed::Gen1));
+        assert_eq!(PcieLinkSpeed::decode(2), Some(PcieLinkSpeed::Gen2));
+        assert_eq!(PcieLinkSpeed::decode(3), Some(PcieLinkSpeed::Gen3));
+        // Reserved, and Gen4 and later which the enum does not cover yet.
+        assert_eq!(PcieLinkSpeed::decode(0), None);
+        assert_eq!(PcieLinkSpeed::decode(4), None);
+        assert_eq!(PcieLinkSpeed::Gen2.mega_transfers(), 5000);
+    }
+
+    #[test]
+    fn decodes_link_speed_and_width() {
+        // Gen2 x4 with Link Training and Slot Clock Configuration set.
+        let lnksta = 2 | (4 << 4) | (1 << 11) | (1 << 12);
+        assert_eq!(
+            LinkStatus::decode(lnksta),
+            Some(LinkStatus {
+                speed: PcieLinkSpeed::Gen2,
+                width: 4,
+            })
+        );
+        assert_eq!(LinkStatus::decode(1 | (16 << 4)).map(|s| s.width), Some(16));
+        assert_eq!(LinkStatus::decode(4 << 4), None);
+    }
+}
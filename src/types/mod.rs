@@ -5,13 +5,23 @@ use bit_field::BitField;
 use pci_types::{Bar, ConfigRegionAccess, EndpointHeader, PciHeader};
 
 mod bar;
+mod capability;
+mod command;
+mod express;
+mod msi;
+mod msix;
 
 pub use bar::*;
+pub use capability::*;
+pub use command::*;
+pub use express::*;
+pub use msi::*;
+pub use msix::*;
 pub use pci_types::{
     capability::PciCapability, device_type::DeviceType, CommandRegister, PciAddress, StatusRegister,
 };
 
-use crate::{Chip, RootComplex};
+use crate::{Chip, DeviceKind, RootComplex};
 
 macro_rules! struct_header {
     ($name: ident, $($more: tt)*) => {
@@ -74,6 +84,19 @@ struct_header!(Endpoint,
     pub capabilities: Vec<PciCapability>
 );
 
+impl Endpoint {
+    /// Reads the revision ID (config offset 0x08) from the device.
+    pub fn revision_id<C: Chip>(&self, root: &RootComplex<C>) -> u8 {
+        root.read_config_as(self.address, 0x08)
+    }
+
+    /// Identifies the NIC family and stepping, `None` if the driver does not
+    /// support the device.
+    pub fn device_kind<C: Chip>(&self, root: &RootComplex<C>) -> Option<DeviceKind> {
+        DeviceKind::detect(self.vendor_id, self.device_id, self.revision_id(root))
+    }
+}
+
 impl BarHeader for EndpointHeader {
     fn read_bar<C: crate::Chip>(&self, slot: usize, access: &crate::RootComplex<C>) -> Option<Bar> {
         self.bar(slot as u8, access)
@@ -116,6 +139,15 @@ struct_header!(PciPciBridge,
 );
 
 impl PciPciBridge {
+    /// Heuristic for a non-transparent bridge showing a type 1 header: any
+    /// class code other than PCI-to-PCI bridge (06/04) or semi-transparent
+    /// bridge (06/09), typically "other bridge" (06/80) as used by NT ports.
+    /// Such a bridge leads into another host's address space, so the devices
+    /// behind it are not ours to enumerate.
+    pub fn is_non_transparent(&self) -> bool {
+        !(self.base_class == 0x06 && matches!(self.sub_class, 0x04 | 0x09))
+    }
+
     pub fn update_bus_number<F>(&self, access: impl ConfigRegionAccess, f: F)
     where
         F: FnOnce(BusNumber) -> BusNumber,
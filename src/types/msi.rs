@@ -0,0 +1,84 @@
+use bit_field::BitField;
+use pci_types::PciAddress;
+
+use crate::{Chip, DError, Endpoint, RootComplex, CAP_ID_MSI};
+
+/// Message control bits, in the upper half of the capability header.
+const MSI_CTRL_ENABLE: usize = 16;
+const MSI_CTRL_MULTIPLE_ENABLE: core::ops::Range<usize> = 16 + 4..16 + 7;
+const MSI_CTRL_64BIT: usize = 16 + 7;
+
+/// The MSI capability of a function, see [`Endpoint::msi_capability`]. Only
+/// a single vector is used.
+#[derive(Debug, Clone)]
+pub struct MsiCapability {
+    address: PciAddress,
+    offset: u16,
+    is_64bit: bool,
+}
+
+impl Endpoint {
+    /// Reads the MSI capability, `None` if the function has none.
+    pub fn msi_capability<C: Chip>(&self, root: &RootComplex<C>) -> Option<MsiCapability> {
+        let offset = self.find_capability(root, CAP_ID_MSI)?;
+        let header = root.read_config(self.address, offset);
+        Some(MsiCapability {
+            address: self.address,
+            offset,
+            is_64bit: header.get_bit(MSI_CTRL_64BIT),
+        })
+    }
+}
+
+impl MsiCapability {
+    /// Whether the function takes 64-bit message addresses.
+    pub fn is_64bit(&self) -> bool {
+        self.is_64bit
+    }
+
+    /// Programs the message address and data and sets MSI Enable, with one
+    /// vector enabled. Fails with [`DError::InvalidParameter`] for an address
+    /// above 4GB on a function with 32-bit addressing.
+    pub fn enable<C: Chip>(
+        &self,
+        root: &mut RootComplex<C>,
+        addr: u64,
+        data: u16,
+    ) -> Result<(), DError> {
+        if !self.is_64bit && addr > u32::MAX as u64 {
+            return Err(DError::InvalidParameter);
+        }
+        self.disable(root);
+
+        root.write_config(self.address, self.offset + 0x04, addr as u32);
+        let data_offset = if self.is_64bit {
+            root.write_config(self.address, self.offset + 0x08, (addr >> 32) as u32);
+            self.offset + 0x0c
+        } else {
+            self.offset + 0x08
+        };
+        // Only the low half is message data, the rest is reserved or the
+        // extended message data some functions implement.
+        let mut word = root.read_config(self.address, data_offset);
+        word.set_bits(0..16, data as u32);
+        root.write_config(self.address, data_offset, word);
+
+        let mut header = root.read_config(self.address, self.offset);
+        header.set_bits(MSI_CTRL_MULTIPLE_ENABLE, 0);
+        header.set_bit(MSI_CTRL_ENABLE, true);
+        root.write_config(self.address, self.offset, header);
+        Ok(())
+    }
+
+    /// Clears MSI Enable, the function falls back to INTx.
+    pub fn disable<C: Chip>(&self, root: &mut RootComplex<C>) {
+        let mut header = root.read_config(self.address, self.offset);
+        header.set_bit(MSI_CTRL_ENABLE, false);
+        root.write_config(self.address, self.offset, header);
+    }
+
+    pub fn is_enabled<C: Chip>(&self, root: &RootComplex<C>) -> bool {
+        root.read_config(self.address, self.offset)
+            .get_bit(MSI_CTRL_ENABLE)
+    }
+}
@@ -0,0 +1,202 @@
+use core::ptr::NonNull;
+
+use bit_field::BitField;
+use pci_types::PciAddress;
+
+use crate::{BarVec, Chip, DError, Endpoint, RootComplex, CAP_ID_MSIX};
+
+/// Size of one MSI-X table entry: address low/high, data, vector control.
+const MSIX_ENTRY_SIZE: usize = 16;
+/// Mask bit in the vector control word of a table entry.
+const MSIX_VECTOR_MASKED: u32 = 1;
+/// Message control bits, in the upper half of the capability header.
+const MSIX_CTRL_FUNCTION_MASK: usize = 16 + 14;
+const MSIX_CTRL_ENABLE: usize = 16 + 15;
+
+/// The MSI-X capability of a function, see [`Endpoint::msix_capability`].
+///
+/// The vector table and the pending bit array (PBA) live in memory BARs:
+/// the BAR indicator (BIR) names the BAR register and the offset is added to
+/// its address. [`MsiXCapability::table_address`] resolves this against the
+/// function's [`BarVec`]; for 64-bit BARs the BIR names the lower register
+/// of the pair. Map the returned range and hand it to
+/// [`MsiXCapability::table`] to program vectors.
+#[derive(Debug, Clone)]
+pub struct MsiXCapability {
+    address: PciAddress,
+    offset: u16,
+    table_size: u16,
+    table_bir: u8,
+    table_offset: u32,
+    pba_bir: u8,
+    pba_offset: u32,
+}
+
+impl Endpoint {
+    /// Reads the MSI-X capability, `None` if the function has none.
+    pub fn msix_capability<C: Chip>(&self, root: &RootComplex<C>) -> Option<MsiXCapability> {
+        let offset = self.find_capability(root, CAP_ID_MSIX)?;
+        let header = root.read_config(self.address, offset);
+        let table = root.read_config(self.address, offset + 0x04);
+        let pba = root.read_config(self.address, offset + 0x08);
+        Some(MsiXCapability {
+            address: self.address,
+            offset,
+            table_size: header.get_bits(16..27) as u16 + 1,
+            table_bir: table.get_bits(0..3) as u8,
+            table_offset: table & !0x7,
+            pba_bir: pba.get_bits(0..3) as u8,
+            pba_offset: pba & !0x7,
+        })
+    }
+}
+
+impl MsiXCapability {
+    /// Number of vectors in the table.
+    pub fn table_size(&self) -> u16 {
+        self.table_size
+    }
+
+    pub fn table_bir(&self) -> u8 {
+        self.table_bir
+    }
+
+    pub fn pba_bir(&self) -> u8 {
+        self.pba_bir
+    }
+
+    /// Bus address of the vector table, `None` if its BAR is not a
+    /// configured memory BAR.
+    pub fn table_address(&self, bar: &BarVec) -> Option<u64> {
+        Some(bar_address(bar, self.table_bir)? + self.table_offset as u64)
+    }
+
+    /// Bus address of the pending bit array.
+    pub fn pba_address(&self, bar: &BarVec) -> Option<u64> {
+        Some(bar_address(bar, self.pba_bir)? + self.pba_offset as u64)
+    }
+
+    /// Bytes of BAR space the vector table takes.
+    pub fn table_len(&self) -> usize {
+        self.table_size as usize * MSIX_ENTRY_SIZE
+    }
+
+    /// Sets or clears MSI-X Enable in the message control register. While
+    /// enabled the function signals interrupts through the table only.
+    pub fn set_enabled<C: Chip>(&self, root: &mut RootComplex<C>, enable: bool) {
+        self.update_control(root, MSIX_CTRL_ENABLE, enable);
+    }
+
+    /// Sets or clears the Function Mask, masking all vectors at once.
+    pub fn set_function_mask<C: Chip>(&self, root: &mut RootComplex<C>, mask: bool) {
+        self.update_control(root, MSIX_CTRL_FUNCTION_MASK, mask);
+    }
+
+    fn update_control<C: Chip>(&self, root: &mut RootComplex<C>, bit: usize, value: bool) {
+        let mut header = root.read_config(self.address, self.offset);
+        header.set_bit(bit, value);
+        root.write_config(self.address, self.offset, header);
+    }
+
+    /// Accesses the vector table mapped at `table`.
+    ///
+    /// # Safety
+    ///
+    /// `table` must be a device mapping of [`MsiXCapability::table_len`]
+    /// bytes at [`MsiXCapability::table_address`].
+    pub unsafe fn table(&self, table: NonNull<u8>) -> MsiXTable {
+        MsiXTable {
+            base: table.cast(),
+            size: self.table_size,
+        }
+    }
+
+    /// Accesses the pending bit array mapped at `pba`.
+    ///
+    /// # Safety
+    ///
+    /// `pba` must be a device mapping of the PBA at
+    /// [`MsiXCapability::pba_address`], one bit per vector in 64-bit words.
+    pub unsafe fn pba(&self, pba: NonNull<u8>) -> MsiXPba {
+        MsiXPba {
+            base: pba.cast(),
+            size: self.table_size,
+        }
+    }
+}
+
+/// Address of the memory BAR register `bir`. 64-bit BARs are stored per
+/// register pair in [`BarVec`].
+fn bar_address(bar: &BarVec, bir: u8) -> Option<u64> {
+    let bir = bir as usize;
+    match bar {
+        BarVec::Memory32(bars) => bars.iter().nth(bir)?.as_ref().map(|b| b.address as u64),
+        BarVec::Memory64(bars) if bir.is_multiple_of(2) => {
+            bars.iter().nth(bir / 2)?.as_ref().map(|b| b.address)
+        }
+        _ => None,
+    }
+}
+
+/// A mapped MSI-X vector table, see [`MsiXCapability::table`].
+pub struct MsiXTable {
+    base: NonNull<u32>,
+    size: u16,
+}
+
+impl MsiXTable {
+    /// Programs the message address and data of `vector`. The vector is
+    /// masked while it is rewritten and left masked, unmask it with
+    /// [`MsiXTable::set_masked`].
+    pub fn set_vector(&mut self, vector: u16, addr: u64, data: u32) -> Result<(), DError> {
+        let entry = self.entry(vector)?;
+        unsafe {
+            entry.add(3).write_volatile(MSIX_VECTOR_MASKED);
+            entry.write_volatile(addr as u32);
+            entry.add(1).write_volatile((addr >> 32) as u32);
+            entry.add(2).write_volatile(data);
+        }
+        Ok(())
+    }
+
+    /// Masks or unmasks `vector`.
+    pub fn set_masked(&mut self, vector: u16, masked: bool) -> Result<(), DError> {
+        let entry = self.entry(vector)?;
+        unsafe {
+            entry
+                .add(3)
+                .write_volatile(masked as u32 * MSIX_VECTOR_MASKED)
+        };
+        Ok(())
+    }
+
+    pub fn is_masked(&self, vector: u16) -> Result<bool, DError> {
+        let entry = self.entry(vector)?;
+        Ok(unsafe { entry.add(3).read_volatile() } & MSIX_VECTOR_MASKED != 0)
+    }
+
+    fn entry(&self, vector: u16) -> Result<*mut u32, DError> {
+        if vector >= self.size {
+            return Err(DError::InvalidParameter);
+        }
+        let words = vector as usize * MSIX_ENTRY_SIZE / size_of::<u32>();
+        Ok(unsafe { self.base.as_ptr().add(words) })
+    }
+}
+
+/// A mapped MSI-X pending bit array, see [`MsiXCapability::pba`].
+pub struct MsiXPba {
+    base: NonNull<u64>,
+    size: u16,
+}
+
+impl MsiXPba {
+    /// Whether `vector` has a message pending while masked.
+    pub fn is_pending(&self, vector: u16) -> Result<bool, DError> {
+        if vector >= self.size {
+            return Err(DError::InvalidParameter);
+        }
+        let word = unsafe { self.base.as_ptr().add(vector as usize / 64).read_volatile() };
+        Ok(word.get_bit(vector as usize % 64))
+    }
+}
@@ -4,9 +4,10 @@
 
 extern crate alloc;
 extern crate bare_test;
-use bare_test::time::spin_delay;
-use core::time::Duration;
-use pcie::{impl_trait, osal::Kernel};
+use alloc::alloc::{alloc_zeroed, dealloc, Layout};
+use bare_test::time::{since_boot, spin_delay};
+use core::{ptr::NonNull, time::Duration};
+use pcie::{impl_trait, osal::Kernel, DmaRegion};
 
 #[bare_test::tests]
 mod tests {
@@ -15,11 +16,10 @@ mod tests {
         globals::{global_val, PlatformInfoKind},
         mem::iomap,
         println,
-        time::spin_delay,
     };
     use core::time::Duration;
     use log::info;
-    use pcie::{CommandRegister, Igb, RootComplexGeneric, SimpleBarAllocator};
+    use pcie::{Igb, RootComplexGeneric, SimpleBarAllocator};
 
     #[test]
     fn test_iter() {
@@ -28,11 +28,9 @@ mod tests {
         let mut igb = get_igb().unwrap();
         igb.open().unwrap();
 
-        while !igb.status().link_up {
-            spin_delay(Duration::from_secs(1));
-        }
+        let status = igb.wait_for_link(Duration::from_secs(10)).unwrap();
 
-        info!("status: {:#?}", igb.status());
+        info!("status: {:#?}", status);
         println!("test passed!");
 
         fn get_igb() -> Option<Igb> {
@@ -74,47 +72,24 @@ mod tests {
                 println!("{}", header);
             }
 
-            for header in root.enumerate_keep_bar(None) {
-                if let pcie::Header::Endpoint(endpoint) = header.header {
-                    if !Igb::check_vid_did(endpoint.vendor_id, endpoint.device_id) {
-                        continue;
-                    }
+            let device = root.find_and_map(Igb::check_vid_did, |addr, size| {
+                println!("bar0: {:#x}", addr);
+                iomap(addr.into(), size)
+            })?;
 
-                    endpoint.update_command(header.root, |cmd| {
-                        cmd | CommandRegister::IO_ENABLE
-                            | CommandRegister::MEMORY_ENABLE
-                            | CommandRegister::BUS_MASTER_ENABLE
-                    });
-
-                    let bar_addr;
-                    let bar_size;
-                    match endpoint.bar {
-                        pcie::BarVec::Memory32(bar_vec_t) => {
-                            let bar0 = bar_vec_t[0].as_ref().unwrap();
-                            bar_addr = bar0.address as usize;
-                            bar_size = bar0.size as usize;
-                        }
-                        pcie::BarVec::Memory64(bar_vec_t) => {
-                            let bar0 = bar_vec_t[0].as_ref().unwrap();
-                            bar_addr = bar0.address as usize;
-                            bar_size = bar0.size as usize;
-                        }
-                        pcie::BarVec::Io(_bar_vec_t) => todo!(),
-                    };
-
-                    println!("bar0: {:#x}", bar_addr);
-
-                    let addr = iomap(bar_addr.into(), bar_size);
-
-                    let igb = Igb::new(addr).unwrap();
-                    return Some(igb);
-                }
-            }
-            None
+            Igb::new(device.bar0).ok()
         }
     }
 }
 
+/// Alignment of the DMA memory the test hands to the driver. Every region
+/// uses it, so `dma_dealloc` rebuilds the same layout; larger alignments
+/// are refused.
+const DMA_ALIGN: usize = 0x1000;
+/// bare-test maps RAM linearly at this offset, the bus address is the
+/// physical one.
+const LINER_OFFSET: usize = 0xffff_f000_0000_0000;
+
 struct KernelImpl;
 
 impl_trait! {
@@ -122,5 +97,27 @@ impl_trait! {
         fn sleep(duration: Duration) {
             spin_delay(duration);
         }
+
+        fn now() -> Duration {
+            since_boot()
+        }
+
+        fn dma_alloc(size: usize, align: usize) -> Option<DmaRegion> {
+            if align > DMA_ALIGN {
+                return None;
+            }
+            let layout = Layout::from_size_align(size, DMA_ALIGN).ok()?;
+            let virt = NonNull::new(unsafe { alloc_zeroed(layout) })?;
+            Some(DmaRegion {
+                virt,
+                bus: (virt.as_ptr() as usize - LINER_OFFSET) as u64,
+                size,
+            })
+        }
+
+        fn dma_dealloc(region: DmaRegion) {
+            let layout = Layout::from_size_align(region.size, DMA_ALIGN).unwrap();
+            unsafe { dealloc(region.virt.as_ptr(), layout) };
+        }
     }
 }